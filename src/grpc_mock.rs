@@ -0,0 +1,915 @@
+//! Helpers for working with gRPC wire-format frames directly.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+#[cfg(feature = "compression")]
+use flate2::read::GzDecoder;
+use http::{
+    header::{HeaderName, CONTENT_TYPE, TE},
+    HeaderMap, HeaderValue, Method, StatusCode, Uri,
+};
+use prost::{Message, Name};
+use std::fmt::Debug;
+#[cfg(feature = "compression")]
+use std::io::Read;
+#[cfg(feature = "compression")]
+use tonic::codec::CompressionEncoding;
+use tonic::{metadata::MetadataMap, Code, Status};
+
+use crate::{
+    mock::{write_frame, HEADER_SIZE},
+    MockBody,
+};
+
+mod server;
+
+pub use server::MockGrpcServer;
+
+const GRPC_CONTENT_TYPE: &str = "application/grpc+proto";
+
+/// Decode every gRPC frame contained in a single buffer
+///
+/// Frames are parsed one after another until the buffer is consumed. A frame that fails to
+/// decode yields an `Err` without stopping the parse; a truncated frame yields an `Err` and ends
+/// it, since the next frame boundary is unknown. Compressed frames are gunzipped when the
+/// `compression` feature is enabled and rejected as `Unimplemented` otherwise.
+///
+/// Usage:
+/// ```
+/// use bytes::{Bytes, BytesMut};
+/// use http_body::Body;
+/// use prost::Message;
+/// use tonic_mock::{grpc_mock::decode_grpc_message_streaming, MockBody};
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// // coalesce the frames of a mock body into one buffer
+/// let mut body = MockBody::new(vec![Event { id: 1 }, Event { id: 2 }]);
+/// let mut buf = BytesMut::new();
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     while let Some(frame) = body.data().await {
+///         buf.extend_from_slice(&frame.unwrap());
+///     }
+/// });
+///
+/// let events = decode_grpc_message_streaming::<Event>(&buf.freeze());
+/// assert_eq!(events.len(), 2);
+/// assert_eq!(events[1].as_ref().unwrap(), &Event { id: 2 });
+/// ```
+///
+/// Malformed input:
+/// ```
+/// use bytes::{BufMut, Bytes, BytesMut};
+/// use tonic::Code;
+/// use tonic_mock::grpc_mock::{decode_grpc_message_streaming, encode_grpc_batch};
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let codes = |bytes: &[u8]| -> Vec<Option<Code>> {
+///     decode_grpc_message_streaming::<Event>(&Bytes::copy_from_slice(bytes))
+///         .iter()
+///         .map(|v| v.as_ref().err().map(|e| e.code()))
+///         .collect()
+/// };
+///
+/// // an undecodable payload doesn't stop the parse, a truncated payload ends it
+/// let mut buf = BytesMut::new();
+/// buf.put_slice(&encode_grpc_batch(&[Event { id: 1 }]));
+/// buf.put_slice(b"\x00\x00\x00\x00\x01\xff");
+/// buf.put_slice(&encode_grpc_batch(&[Event { id: 3 }]));
+/// buf.put_slice(b"\x00\x00\x00\x00\x09\x08\x04");
+/// buf.put_slice(&encode_grpc_batch(&[Event { id: 5 }]));
+/// assert_eq!(codes(&buf), vec![None, Some(Code::Internal), None, Some(Code::Internal)]);
+///
+/// // a truncated header
+/// assert_eq!(codes(b"\x00\x00\x00"), vec![Some(Code::Internal)]);
+///
+/// // an invalid compression flag
+/// assert_eq!(codes(b"\x02\x00\x00\x00\x00"), vec![Some(Code::Internal)]);
+///
+/// // a compressed frame: gunzipped with the `compression` feature, unsupported without it
+/// let expected = if cfg!(feature = "compression") {
+///     Code::Internal // not valid gzip
+/// } else {
+///     Code::Unimplemented
+/// };
+/// assert_eq!(codes(b"\x01\x00\x00\x00\x02\xde\xad"), vec![Some(expected)]);
+/// ```
+pub fn decode_grpc_message_streaming<T>(bytes: &Bytes) -> Vec<Result<T, Status>>
+where
+    T: Message + Default + Debug,
+{
+    split_frames(bytes)
+        .into_iter()
+        .map(|payload| {
+            payload.and_then(|p| T::decode(p).map_err(|e| Status::internal(e.to_string())))
+        })
+        .collect()
+}
+
+/// Encode `messages` as consecutive uncompressed gRPC frames in a single buffer
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic_mock::grpc_mock::{decode_grpc_batch, encode_grpc_batch};
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let bytes = encode_grpc_batch(&[Event { id: 1 }, Event { id: 2 }]);
+/// let events: Vec<Event> = decode_grpc_batch(&bytes)
+///     .into_iter()
+///     .map(Result::unwrap)
+///     .collect();
+/// assert_eq!(events, vec![Event { id: 1 }, Event { id: 2 }]);
+/// ```
+pub fn encode_grpc_batch<T: Message>(messages: &[T]) -> Bytes {
+    let mut buf = BytesMut::new();
+    for msg in messages {
+        MockBody::encode_into(&mut buf, msg);
+    }
+    buf.freeze()
+}
+
+/// Like [`encode_grpc_batch`], but each frame is compressed with `encoding` and flagged as such
+///
+/// # Panics
+///
+/// Only [`CompressionEncoding::Gzip`] is supported; any other encoding panics.
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic::codec::CompressionEncoding;
+/// use tonic_mock::grpc_mock::{decode_grpc_batch, encode_grpc_batch_compressed};
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(string, tag = "1")]
+///     pub data: String,
+/// }
+///
+/// let event = Event { data: "a".repeat(100) };
+/// let bytes = encode_grpc_batch_compressed(&[event.clone()], CompressionEncoding::Gzip);
+/// assert_eq!(bytes[0], 1);
+///
+/// let decoded = decode_grpc_batch::<Event>(&bytes);
+/// assert_eq!(decoded[0].as_ref().unwrap(), &event);
+/// ```
+#[cfg(feature = "compression")]
+pub fn encode_grpc_batch_compressed<T: Message>(
+    messages: &[T],
+    encoding: CompressionEncoding,
+) -> Bytes {
+    let mut buf = BytesMut::new();
+    for msg in messages {
+        MockBody::encode_compressed_into(&mut buf, msg, encoding);
+    }
+    buf.freeze()
+}
+
+/// Decode every frame of a buffer produced by [`encode_grpc_batch`]
+///
+/// This is [`decode_grpc_message_streaming`] under the name that pairs with the encoder.
+pub fn decode_grpc_batch<T>(bytes: &Bytes) -> Vec<Result<T, Status>>
+where
+    T: Message + Default + Debug,
+{
+    decode_grpc_message_streaming(bytes)
+}
+
+/// Call `handler` with the raw payload of a framed gRPC request and frame its raw response
+///
+/// `request_bytes` must hold exactly one gRPC frame, otherwise `InvalidArgument` is returned; the
+/// handler receives the payload without the 5-byte header and the bytes it returns are framed
+/// again. This is meant for testing codecs that do not go through prost.
+///
+/// Usage:
+/// ```
+/// use bytes::Bytes;
+/// use tonic_mock::grpc_mock::mock_grpc_call_raw;
+///
+/// // a frame holding the raw payload "ping"
+/// let request = Bytes::from_static(b"\x00\x00\x00\x00\x04ping");
+/// let response = mock_grpc_call_raw("example.RawService", "Ping", request, |payload| {
+///     assert_eq!(payload, b"ping");
+///     Ok(Bytes::from_static(b"pong"))
+/// })
+/// .unwrap();
+/// assert_eq!(response.as_ref(), b"\x00\x00\x00\x00\x04pong");
+///
+/// // trailing bytes after the frame are rejected
+/// let request = Bytes::from_static(b"\x00\x00\x00\x00\x04ping\x00");
+/// let err = mock_grpc_call_raw("example.RawService", "Ping", request, |_| unreachable!());
+/// assert_eq!(err.unwrap_err().code(), tonic::Code::InvalidArgument);
+/// ```
+pub fn mock_grpc_call_raw(
+    service_name: &str,
+    method_name: &str,
+    request_bytes: Bytes,
+    handler: impl FnOnce(&[u8]) -> Result<Bytes, Status>,
+) -> Result<Bytes, Status> {
+    let mut frames = split_frames(&request_bytes);
+    if frames.len() != 1 {
+        return Err(Status::invalid_argument(format!(
+            "request for {}/{} must hold exactly one gRPC frame, found {}",
+            service_name,
+            method_name,
+            frames.len()
+        )));
+    }
+    let payload = frames.remove(0)?;
+    let response = handler(&payload)?;
+
+    let mut buf = BytesMut::with_capacity(HEADER_SIZE + response.len());
+    write_frame(&mut buf, false, |buf| buf.put_slice(&response));
+    Ok(buf.freeze())
+}
+
+/// Encode `message` as a gRPC frame and decode it back
+///
+/// Usage:
+/// ```
+/// use tonic_mock::grpc_mock::grpc_roundtrip;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// assert_eq!(grpc_roundtrip(&Event { id: 3 }).unwrap(), Event { id: 3 });
+/// ```
+pub fn grpc_roundtrip<T>(message: &T) -> Result<T, Status>
+where
+    T: Message + Default,
+{
+    let frame = MockBody::encode(message);
+    match split_frames(&frame).as_slice() {
+        [payload] => payload
+            .clone()
+            .and_then(|p| T::decode(p).map_err(|e| Status::internal(e.to_string()))),
+        frames => Err(Status::internal(format!(
+            "expected one gRPC frame, decoded {}",
+            frames.len()
+        ))),
+    }
+}
+
+/// Assert that `message` survives being framed, encoded and decoded again
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic_mock::grpc_mock::assert_grpc_encoding_roundtrip;
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+///     #[prost(map = "string, string", tag = "2")]
+///     pub labels: std::collections::HashMap<String, String>,
+/// }
+///
+/// let mut event = Event { id: 1, ..Default::default() };
+/// event.labels.insert("env".into(), "test".into());
+/// assert_grpc_encoding_roundtrip(event);
+/// ```
+pub fn assert_grpc_encoding_roundtrip<T>(message: T)
+where
+    T: Message + Default + PartialEq + Debug + Clone,
+{
+    match grpc_roundtrip(&message) {
+        Ok(decoded) => assert_eq!(decoded, message, "message changed after roundtrip"),
+        Err(status) => panic!("failed to decode {:?}: {:?}", message, status),
+    }
+}
+
+/// Assert that decoding and re-encoding `message` `iterations` times reproduces its first encoding
+///
+/// Each iteration decodes the first encoding into a fresh value, so encodings that depend on
+/// in-memory state are caught. The usual culprit is a `HashMap`-backed map field with more than
+/// one entry, whose order changes between decodes; declare such fields as `btree_map` to make
+/// the encoding deterministic.
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic_mock::grpc_mock::assert_grpc_encoding_stable;
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+///     #[prost(btree_map = "string, string", tag = "2")]
+///     pub labels: std::collections::BTreeMap<String, String>,
+/// }
+///
+/// let labels = (0..16).map(|i| (i.to_string(), "v".to_string())).collect();
+/// assert_grpc_encoding_stable(Event { id: 1, labels }, 10);
+/// ```
+///
+/// A `HashMap` field with many entries is reported:
+/// ```should_panic
+/// use prost::Message;
+/// use tonic_mock::grpc_mock::assert_grpc_encoding_stable;
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(map = "string, string", tag = "1")]
+///     pub labels: std::collections::HashMap<String, String>,
+/// }
+///
+/// let labels = (0..16).map(|i| (i.to_string(), "v".to_string())).collect();
+/// assert_grpc_encoding_stable(Event { labels }, 10);
+/// ```
+pub fn assert_grpc_encoding_stable<T>(message: T, iterations: usize)
+where
+    T: Message + Default + PartialEq + Debug + Clone,
+{
+    let first = message.encode_to_vec();
+    for i in 1..iterations {
+        let decoded = match T::decode(first.as_slice()) {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("failed to decode {:?}: {}", message, e),
+        };
+        assert_eq!(
+            decoded.encode_to_vec(),
+            first,
+            "re-encoding {} of {:?} differs from the first encoding",
+            i,
+            message
+        );
+    }
+}
+
+// Split a buffer into the payloads of its gRPC frames.
+fn split_frames(bytes: &Bytes) -> Vec<Result<Bytes, Status>> {
+    let mut result = Vec::new();
+    let mut buf = bytes.clone();
+    while buf.has_remaining() {
+        if buf.remaining() < HEADER_SIZE {
+            result.push(Err(Status::internal(format!(
+                "truncated gRPC frame header: {} bytes remaining",
+                buf.remaining()
+            ))));
+            break;
+        }
+
+        let compressed = buf.get_u8();
+        let len = buf.get_u32() as usize;
+        if buf.remaining() < len {
+            result.push(Err(Status::internal(format!(
+                "truncated gRPC frame: expected {} bytes, got {}",
+                len,
+                buf.remaining()
+            ))));
+            break;
+        }
+
+        let payload = buf.split_to(len);
+        result.push(match compressed {
+            0 => Ok(payload),
+            1 => decompress(payload),
+            flag => Err(Status::internal(format!(
+                "invalid gRPC compression flag: {}",
+                flag
+            ))),
+        });
+    }
+    result
+}
+
+// Compressed frames are assumed to be gzip, the only encoding the mock bodies produce.
+#[cfg(feature = "compression")]
+fn decompress(payload: Bytes) -> Result<Bytes, Status> {
+    let mut decoded = Vec::new();
+    GzDecoder::new(payload.as_ref())
+        .read_to_end(&mut decoded)
+        .map_err(|e| Status::internal(format!("failed to decompress gRPC frame: {}", e)))?;
+    Ok(Bytes::from(decoded))
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_: Bytes) -> Result<Bytes, Status> {
+    Err(Status::unimplemented(
+        "compressed gRPC frames require the `compression` feature",
+    ))
+}
+
+/// A gRPC service and method name pair, usable in `const` items to avoid repeating string pairs
+///
+/// Usage:
+/// ```
+/// use tonic_mock::grpc_mock::ServiceMethod;
+///
+/// const SERVICE_METHOD_EXAMPLE: ServiceMethod = ServiceMethod::new("example.TestService", "TestMethod");
+///
+/// assert_eq!(SERVICE_METHOD_EXAMPLE.to_uri().path(), "/example.TestService/TestMethod");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ServiceMethod {
+    pub service: &'static str,
+    pub method: &'static str,
+}
+
+impl ServiceMethod {
+    pub const fn new(service: &'static str, method: &'static str) -> Self {
+        Self { service, method }
+    }
+
+    /// The request path, `/{service}/{method}`.
+    pub fn path(&self) -> String {
+        format!("/{}/{}", self.service, self.method)
+    }
+
+    /// The request path as a `Uri`. Panics if the names are not valid in a URI path.
+    pub fn to_uri(&self) -> Uri {
+        self.path()
+            .parse()
+            .expect("invalid gRPC service or method name")
+    }
+}
+
+/// Split a gRPC request path of the form `/package.Service/Method` into service and method
+///
+/// Usage:
+/// ```
+/// use tonic_mock::grpc_mock::extract_method_from_path;
+///
+/// assert_eq!(
+///     extract_method_from_path("/example.TestService/TestMethod"),
+///     Some(("example.TestService", "TestMethod"))
+/// );
+/// assert_eq!(extract_method_from_path("/example.TestService"), None);
+/// ```
+pub fn extract_method_from_path(path: &str) -> Option<(&str, &str)> {
+    let (service, method) = path.strip_prefix('/')?.split_once('/')?;
+    if service.is_empty() || method.is_empty() || method.contains('/') {
+        return None;
+    }
+    Some((service, method))
+}
+
+/// Return the service part of a gRPC request path of the form `/package.Service/Method`
+///
+/// Usage:
+/// ```
+/// use tonic_mock::grpc_mock::extract_service_from_path;
+///
+/// assert_eq!(extract_service_from_path("/example.TestService/TestMethod"), Some("example.TestService"));
+/// ```
+pub fn extract_service_from_path(path: &str) -> Option<&str> {
+    extract_method_from_path(path).map(|(service, _)| service)
+}
+
+/// Assert that `uri` addresses `expected_service`/`expected_method`
+///
+/// The panic message shows the parsed service and method when they differ.
+///
+/// Usage:
+/// ```
+/// use tonic_mock::grpc_mock::{assert_grpc_uri_matches, ServiceMethod};
+///
+/// let uri = ServiceMethod::new("example.TestService", "TestMethod").to_uri();
+/// assert_grpc_uri_matches(&uri, "example.TestService", "TestMethod");
+/// ```
+pub fn assert_grpc_uri_matches(uri: &Uri, expected_service: &str, expected_method: &str) {
+    match extract_method_from_path(uri.path()) {
+        Some((service, method)) => assert!(
+            service == expected_service && method == expected_method,
+            "uri {} addresses {}/{}, expected {}/{}",
+            uri,
+            service,
+            method,
+            expected_service,
+            expected_method
+        ),
+        None => panic!(
+            "uri {} is not a gRPC path, expected /{}/{}",
+            uri, expected_service, expected_method
+        ),
+    }
+}
+
+/// Assert that the path of `uri` is exactly `expected_path`
+///
+/// Usage:
+/// ```
+/// use tonic_mock::grpc_mock::assert_grpc_uri_path;
+///
+/// let uri: http::Uri = "http://localhost/example.TestService/TestMethod".parse().unwrap();
+/// assert_grpc_uri_path(&uri, "/example.TestService/TestMethod");
+/// ```
+pub fn assert_grpc_uri_path(uri: &Uri, expected_path: &str) {
+    assert_eq!(uri.path(), expected_path, "unexpected path in uri {}", uri);
+}
+
+/// Build a complete gRPC HTTP response carrying `message`
+///
+/// The response has a `content-type: application/grpc+proto` header plus the given `metadata`,
+/// a body with the framed message, and a trailing `grpc-status: 0`.
+///
+/// Usage:
+/// ```
+/// use http::StatusCode;
+/// use http_body::Body;
+/// use prost::Message;
+/// use tonic_mock::grpc_mock::build_grpc_response;
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let response = build_grpc_response(Event { id: 1 }, StatusCode::OK, &[("x-trace-id", "abc")]);
+/// assert_eq!(response.headers()["content-type"], "application/grpc+proto");
+/// assert_eq!(response.headers()["x-trace-id"], "abc");
+///
+/// let mut body = response.into_body();
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     assert!(body.data().await.is_some());
+///     assert!(body.data().await.is_none());
+///     let trailers = body.trailers().await.unwrap().unwrap();
+///     assert_eq!(trailers["grpc-status"], "0");
+/// });
+/// ```
+pub fn build_grpc_response<T>(
+    message: T,
+    status_code: StatusCode,
+    metadata: &[(&str, &str)],
+) -> http::Response<MockBody>
+where
+    T: Message,
+{
+    let mut trailers = HeaderMap::new();
+    trailers.insert("grpc-status", HeaderValue::from_static("0"));
+    let body = MockBody::new(vec![message]).with_trailers(trailers);
+
+    let mut response = http::Response::new(body);
+    *response.status_mut() = status_code;
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(GRPC_CONTENT_TYPE));
+    for (key, value) in metadata {
+        let name = HeaderName::from_bytes(key.as_bytes()).expect("invalid header name");
+        let value = HeaderValue::from_str(value).expect("invalid header value");
+        headers.append(name, value);
+    }
+    response
+}
+
+/// Build a trailers-only gRPC error response for `status`
+///
+/// The `grpc-status` and `grpc-message` are sent with the headers and the body is empty, which
+/// is how gRPC servers report errors that happen before any message was sent.
+///
+/// Usage:
+/// ```
+/// use tonic::Status;
+/// use tonic_mock::grpc_mock::build_grpc_error_response;
+///
+/// let response = build_grpc_error_response(Status::not_found("missing"));
+/// assert_eq!(response.headers()["grpc-status"], "5");
+/// assert_eq!(response.headers()["grpc-message"], "missing");
+/// assert!(response.body().is_empty());
+/// ```
+pub fn build_grpc_error_response(status: Status) -> http::Response<MockBody> {
+    let mut response = http::Response::new(MockBody::new(Vec::<()>::new()));
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(GRPC_CONTENT_TYPE));
+    status
+        .add_header(headers)
+        .expect("failed to encode grpc status headers");
+    response
+}
+
+/// Build a complete gRPC HTTP request for `service`/`method` carrying `message`
+///
+/// The request is a `POST` to `/{service}/{method}` with the gRPC content type, `te: trailers`
+/// and all entries of `metadata` as headers, which is what tonic `Layer`s and interceptors see.
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic::metadata::MetadataMap;
+/// use tonic_mock::grpc_mock::build_grpc_request_with_metadata;
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let mut metadata = MetadataMap::new();
+/// metadata.insert("authorization", "Bearer token".parse().unwrap());
+///
+/// let request = build_grpc_request_with_metadata("example.EventService", "Push", Event { id: 1 }, &metadata);
+/// assert_eq!(request.method(), http::Method::POST);
+/// assert_eq!(request.uri().path(), "/example.EventService/Push");
+/// assert_eq!(request.headers()["te"], "trailers");
+/// assert_eq!(request.headers()["authorization"], "Bearer token");
+/// assert_eq!(request.body().len(), 1);
+/// ```
+pub fn build_grpc_request_with_metadata<T>(
+    service: &str,
+    method: &str,
+    message: T,
+    metadata: &MetadataMap,
+) -> http::Request<MockBody>
+where
+    T: Message + Default + Send + 'static,
+{
+    let mut request = http::Request::new(MockBody::new(vec![message]));
+    *request.method_mut() = Method::POST;
+    *request.uri_mut() = format!("/{}/{}", service, method)
+        .parse()
+        .expect("invalid gRPC service or method name");
+
+    let headers = request.headers_mut();
+    headers.extend(metadata.clone().into_headers());
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(GRPC_CONTENT_TYPE));
+    headers.insert(TE, HeaderValue::from_static("trailers"));
+    request
+}
+
+/// Assert that a raw gRPC request carries metadata `key` with `expected_value`
+///
+/// Keys are matched case-insensitively. For binary keys (ending in `-bin`) the header is
+/// base64-decoded and compared with the bytes of `expected_value`.
+///
+/// Usage:
+/// ```
+/// use tonic::metadata::{BinaryMetadataValue, MetadataMap};
+/// use tonic_mock::grpc_mock::{assert_grpc_request_metadata, build_grpc_request_with_metadata};
+///
+/// let mut metadata = MetadataMap::new();
+/// metadata.insert("x-user", "alice".parse().unwrap());
+/// metadata.insert_bin("trace-bin", BinaryMetadataValue::from_bytes(b"abc"));
+///
+/// let request = build_grpc_request_with_metadata("example.Service", "Call", (), &metadata);
+/// assert_grpc_request_metadata(&request, "X-User", "alice");
+/// assert_grpc_request_metadata(&request, "trace-bin", "abc");
+/// ```
+///
+/// A binary value that is not valid base64 is reported as such, not as missing:
+/// ```should_panic
+/// use tonic_mock::grpc_mock::assert_grpc_request_metadata;
+///
+/// let request = http::Request::builder().header("trace-bin", "not base64!").body(()).unwrap();
+/// assert_grpc_request_metadata(&request, "trace-bin", "abc");
+/// ```
+pub fn assert_grpc_request_metadata<B>(
+    request: &http::Request<B>,
+    key: &str,
+    expected_value: &str,
+) {
+    let key = key.to_ascii_lowercase();
+    let metadata = MetadataMap::from_headers(request.headers().clone());
+    let actual = if key.ends_with("-bin") {
+        metadata.get_bin(key.as_str()).map(|v| match v.to_bytes() {
+            Ok(bytes) => bytes.to_vec(),
+            Err(e) => panic!(
+                "request metadata {:?} is present but is not valid base64: {}",
+                key, e
+            ),
+        })
+    } else {
+        metadata
+            .get(key.as_str())
+            .map(|v| v.as_encoded_bytes().to_vec())
+    };
+    match actual {
+        Some(actual) => assert_eq!(
+            actual,
+            expected_value.as_bytes(),
+            "request metadata {:?} has value {:?}, expected {:?}",
+            key,
+            String::from_utf8_lossy(&actual),
+            expected_value
+        ),
+        None => panic!(
+            "request metadata {:?} is missing, present keys: {:?}",
+            key,
+            request.headers().keys().collect::<Vec<_>>()
+        ),
+    }
+}
+
+/// Assert that a raw gRPC request does not carry metadata `key`
+///
+/// Usage:
+/// ```
+/// use tonic::metadata::MetadataMap;
+/// use tonic_mock::grpc_mock::{assert_grpc_request_metadata_absent, build_grpc_request_with_metadata};
+///
+/// let request = build_grpc_request_with_metadata("example.Service", "Call", (), &MetadataMap::new());
+/// assert_grpc_request_metadata_absent(&request, "authorization");
+/// ```
+pub fn assert_grpc_request_metadata_absent<B>(request: &http::Request<B>, key: &str) {
+    let key = key.to_ascii_lowercase();
+    assert!(
+        !request.headers().contains_key(key.as_str()),
+        "request metadata {:?} should be absent, found {:?}",
+        key,
+        request.headers().get(key.as_str())
+    );
+}
+
+/// Extract the raw `grpc-status-details-bin` payload of `status`, if it has one
+///
+/// The payload is an encoded `google.rpc.Status` message.
+///
+/// Usage:
+/// ```
+/// use bytes::Bytes;
+/// use tonic::{Code, Status};
+/// use tonic_mock::grpc_mock::extract_grpc_status_details;
+///
+/// let status = Status::with_details(Code::InvalidArgument, "bad", Bytes::from_static(b"\x08\x03"));
+/// assert_eq!(extract_grpc_status_details(&status).unwrap().as_ref(), b"\x08\x03");
+/// assert!(extract_grpc_status_details(&Status::internal("no details")).is_none());
+/// ```
+pub fn extract_grpc_status_details(status: &Status) -> Option<Bytes> {
+    let details = status.details();
+    if details.is_empty() {
+        None
+    } else {
+        Some(Bytes::copy_from_slice(details))
+    }
+}
+
+/// Decode the first detail of type `T` from the `google.rpc.Status` carried by `status`
+///
+/// A detail matches when the full name at the end of its `type_url` equals [`Name::full_name`],
+/// so both `type.googleapis.com/google.rpc.ErrorInfo` and `/google.rpc.ErrorInfo` match
+/// `google.rpc.ErrorInfo`. Returns `NotFound` if no detail has that type.
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic::{Code, Status};
+/// use tonic_mock::grpc_mock::decode_grpc_status_detail;
+///
+/// // google.rpc.ErrorInfo
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct ErrorInfo {
+///     #[prost(string, tag = "1")]
+///     pub reason: String,
+/// }
+/// impl prost::Name for ErrorInfo {
+///     const NAME: &'static str = "ErrorInfo";
+///     const PACKAGE: &'static str = "google.rpc";
+/// }
+///
+/// // google.rpc.Status and google.protobuf.Any, as a server would send them
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Any {
+///     #[prost(string, tag = "1")]
+///     pub type_url: String,
+///     #[prost(bytes = "vec", tag = "2")]
+///     pub value: Vec<u8>,
+/// }
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct RpcStatus {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+///     #[prost(string, tag = "2")]
+///     pub message: String,
+///     #[prost(message, repeated, tag = "3")]
+///     pub details: Vec<Any>,
+/// }
+///
+/// let info = ErrorInfo { reason: "QUOTA".into() };
+/// let rpc_status = RpcStatus {
+///     code: Code::ResourceExhausted as i32,
+///     message: "quota".into(),
+///     details: vec![
+///         Any {
+///             type_url: "type.googleapis.com/google.rpc.RetryInfo".into(),
+///             value: vec![0x0a, 0x00],
+///         },
+///         Any {
+///             type_url: "type.googleapis.com/google.rpc.ErrorInfo".into(),
+///             value: info.encode_to_vec(),
+///         },
+///     ],
+/// };
+/// let status = Status::with_details(Code::ResourceExhausted, "quota", rpc_status.encode_to_vec().into());
+///
+/// assert_eq!(decode_grpc_status_detail::<ErrorInfo>(&status).unwrap(), info);
+/// ```
+pub fn decode_grpc_status_detail<T>(status: &Status) -> Result<T, Status>
+where
+    T: Name + Default,
+{
+    let full_name = T::full_name();
+    let detail = decode_rpc_status(status)?
+        .details
+        .into_iter()
+        .find(|detail| type_name(&detail.type_url) == full_name)
+        .ok_or_else(|| Status::not_found(format!("status has no {} detail", full_name)))?;
+    T::decode(detail.value).map_err(|e| Status::internal(e.to_string()))
+}
+
+/// Describe the `google.rpc.Status` carried by `status`, listing the type of each detail
+///
+/// Meant for assertion messages and test logs.
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic::{Code, Status};
+/// use tonic_mock::grpc_mock::format_grpc_status_detail;
+///
+/// // google.rpc.Status and google.protobuf.Any, as a server would send them
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Any {
+///     #[prost(string, tag = "1")]
+///     pub type_url: String,
+///     #[prost(bytes = "vec", tag = "2")]
+///     pub value: Vec<u8>,
+/// }
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct RpcStatus {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+///     #[prost(string, tag = "2")]
+///     pub message: String,
+///     #[prost(message, repeated, tag = "3")]
+///     pub details: Vec<Any>,
+/// }
+///
+/// let rpc_status = RpcStatus {
+///     code: Code::InvalidArgument as i32,
+///     message: "bad name".into(),
+///     details: vec![Any {
+///         type_url: "type.googleapis.com/google.rpc.BadRequest".into(),
+///         value: vec![],
+///     }],
+/// };
+/// let status = Status::with_details(Code::InvalidArgument, "bad name", rpc_status.encode_to_vec().into());
+///
+/// assert_eq!(
+///     format_grpc_status_detail(&status).unwrap(),
+///     "InvalidArgument: bad name [type.googleapis.com/google.rpc.BadRequest]"
+/// );
+/// ```
+pub fn format_grpc_status_detail(status: &Status) -> Result<String, Status> {
+    let rpc_status = decode_rpc_status(status)?;
+    let types: Vec<&str> = rpc_status
+        .details
+        .iter()
+        .map(|detail| detail.type_url.as_str())
+        .collect();
+    Ok(format!(
+        "{:?}: {} [{}]",
+        Code::from(rpc_status.code),
+        rpc_status.message,
+        types.join(", ")
+    ))
+}
+
+fn decode_rpc_status(status: &Status) -> Result<RpcStatus, Status> {
+    let details = extract_grpc_status_details(status)
+        .ok_or_else(|| Status::not_found("status carries no details"))?;
+    RpcStatus::decode(details).map_err(|e| Status::internal(e.to_string()))
+}
+
+// The full type name is whatever follows the last `/` of a type URL.
+fn type_name(type_url: &str) -> &str {
+    type_url.rsplit('/').next().unwrap_or(type_url)
+}
+
+// google.rpc.Status
+#[derive(Clone, PartialEq, Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<Any>,
+}
+
+// google.protobuf.Any
+#[derive(Clone, PartialEq, Message)]
+struct Any {
+    #[prost(string, tag = "1")]
+    type_url: String,
+    #[prost(bytes = "bytes", tag = "2")]
+    value: Bytes,
+}
@@ -0,0 +1,165 @@
+use bytes::Bytes;
+use http::StatusCode;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Server,
+};
+use prost::Message;
+use std::{collections::HashMap, convert::Infallible, net::SocketAddr, sync::Arc};
+use tokio::task::JoinHandle;
+use tonic::Status;
+
+use super::{
+    build_grpc_error_response, build_grpc_response, decode_grpc_message_streaming,
+    extract_method_from_path,
+};
+use crate::MockBody;
+
+type Handler = Arc<dyn Fn(Bytes) -> http::Response<MockBody> + Send + Sync>;
+
+/// A lightweight gRPC server that dispatches unary calls to registered closures
+///
+/// Calls are routed by their `/service/method` path. Requests for unregistered methods are
+/// answered with `Unimplemented`.
+///
+/// Usage:
+/// ```
+/// use http::uri::PathAndQuery;
+/// use prost::Message;
+/// use tonic::{codec::ProstCodec, transport::Channel, Request};
+/// use tonic_mock::grpc_mock::MockGrpcServer;
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Echo {
+///     #[prost(string, tag = "1")]
+///     pub text: String,
+/// }
+///
+/// let mut server = MockGrpcServer::bind("127.0.0.1:0".parse().unwrap());
+/// server.register("example.EchoService", "Echo", |req: Echo| Ok(req));
+/// let addr = server.local_addr();
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     let handle = server.serve().await;
+///
+///     let channel = Channel::from_shared(format!("http://{}", addr))
+///         .unwrap()
+///         .connect()
+///         .await
+///         .unwrap();
+///     let mut client = tonic::client::Grpc::new(channel);
+///     client.ready().await.unwrap();
+///
+///     let path = PathAndQuery::from_static("/example.EchoService/Echo");
+///     let codec: ProstCodec<Echo, Echo> = ProstCodec::default();
+///     let request = Request::new(Echo { text: "hi".into() });
+///     let response = client.unary(request, path, codec).await.unwrap();
+///     assert_eq!(response.into_inner().text, "hi");
+///
+///     handle.abort();
+/// });
+/// ```
+pub struct MockGrpcServer {
+    listener: std::net::TcpListener,
+    handlers: HashMap<String, Handler>,
+}
+
+impl MockGrpcServer {
+    /// Bind the listening socket. Use port 0 to pick a free port, see [`Self::local_addr`].
+    ///
+    /// Panics if the address cannot be bound.
+    pub fn bind(addr: SocketAddr) -> Self {
+        let listener = std::net::TcpListener::bind(addr).expect("failed to bind mock server");
+        listener
+            .set_nonblocking(true)
+            .expect("failed to set mock server socket to non-blocking");
+        Self {
+            listener,
+            handlers: HashMap::new(),
+        }
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener
+            .local_addr()
+            .expect("mock server has no local address")
+    }
+
+    /// Register the handler for `service`/`method`, replacing any previous one.
+    pub fn register<Req, Resp, F>(&mut self, service: &str, method: &str, handler: F) -> &mut Self
+    where
+        Req: Message + Default + 'static,
+        Resp: Message + 'static,
+        F: Fn(Req) -> Result<Resp, Status> + Send + Sync + 'static,
+    {
+        let handler: Handler = Arc::new(move |body| {
+            let response = match decode_grpc_message_streaming::<Req>(&body)
+                .into_iter()
+                .next()
+            {
+                Some(request) => request.and_then(&handler),
+                None => Err(Status::internal("missing request message")),
+            };
+            match response {
+                Ok(response) => build_grpc_response(response, StatusCode::OK, &[]),
+                Err(status) => build_grpc_error_response(status),
+            }
+        });
+        self.handlers
+            .insert(format!("/{}/{}", service, method), handler);
+        self
+    }
+
+    /// Start serving HTTP/2 on the bound socket. Abort the returned handle to stop the server.
+    pub async fn serve(self) -> JoinHandle<()> {
+        let handlers = Arc::new(self.handlers);
+        let make_svc = make_service_fn(move |_| {
+            let handlers = handlers.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let handlers = handlers.clone();
+                    async move { Ok::<_, Infallible>(dispatch(&handlers, req).await) }
+                }))
+            }
+        });
+
+        let server = Server::from_tcp(self.listener)
+            .expect("failed to start mock server")
+            .http2_only(true)
+            .serve(make_svc);
+        tokio::spawn(async move {
+            let _ = server.await;
+        })
+    }
+}
+
+async fn dispatch(
+    handlers: &HashMap<String, Handler>,
+    req: http::Request<Body>,
+) -> http::Response<MockBody> {
+    let path = req.uri().path().to_string();
+    let (service, method) = match extract_method_from_path(&path) {
+        Some(parts) => parts,
+        None => {
+            return build_grpc_error_response(Status::unimplemented(format!(
+                "malformed gRPC path {}",
+                path
+            )))
+        }
+    };
+    let handler = match handlers.get(&path) {
+        Some(handler) => handler.clone(),
+        None => {
+            return build_grpc_error_response(Status::unimplemented(format!(
+                "no mock handler registered for {}::{}",
+                service, method
+            )))
+        }
+    };
+
+    match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => handler(body),
+        Err(e) => build_grpc_error_response(Status::internal(e.to_string())),
+    }
+}
@@ -1,11 +1,32 @@
-use futures::{Stream, StreamExt};
+// `tonic::Status` is the error type of every gRPC API this crate mirrors, large or not.
+#![allow(clippy::result_large_err)]
+
+use futures::{
+    future::{self, Either},
+    stream, Future, Stream, StreamExt,
+};
 use prost::Message;
-use std::pin::Pin;
-use tonic::{Request, Response, Status, Streaming};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    hash::Hash,
+    pin::Pin,
+    time::{Duration, Instant},
+};
+use tokio::sync::{broadcast, mpsc, oneshot};
+#[cfg(feature = "compression")]
+use tonic::codec::CompressionEncoding;
+use tonic::{metadata::MetadataMap, Code, Request, Response, Status, Streaming};
 
+pub mod grpc_mock;
 mod mock;
+mod request;
+pub mod test_utils;
 
 pub use mock::{MockBody, ProstDecoder};
+pub use request::{
+    RequestBuilder, RequestInterceptor, RequestInterceptorChain, StreamingRequestBuilder,
+};
 
 pub type StreamResponseInner<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send + Sync>>;
 pub type StreamResponse<T> = Response<StreamResponseInner<T>>;
@@ -47,6 +68,173 @@ where
     Request::new(stream)
 }
 
+/// Generate streaming request whose stream fails with `error` at position `error_index`
+///
+/// Messages before `error_index` are delivered normally; reading the message at `error_index`
+/// returns `error` instead.
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic::{Code, Status};
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let events = vec![Event { id: 0 }, Event { id: 1 }, Event { id: 2 }];
+/// let request = tonic_mock::streaming_request_with_error_at(events, 1, Status::internal("bad frame"));
+/// let mut stream = request.into_inner();
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     assert_eq!(stream.message().await.unwrap(), Some(Event { id: 0 }));
+///     assert_eq!(stream.message().await.unwrap_err().code(), Code::Internal);
+/// });
+/// ```
+pub fn streaming_request_with_error_at<T>(
+    messages: Vec<T>,
+    error_index: usize,
+    error: Status,
+) -> Request<Streaming<T>>
+where
+    T: Message + Default + Send + 'static,
+{
+    let body = MockBody::with_error_at(messages, error_index, error);
+    let decoder: ProstDecoder<T> = ProstDecoder::new();
+    let stream = Streaming::new_request(decoder, body, None, None);
+
+    Request::new(stream)
+}
+
+/// Generate streaming request whose messages are pulled from `stream` as the service reads them
+///
+/// Usage:
+/// ```
+/// use futures::stream;
+/// use prost::Message;
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let events = stream::iter((0..3).map(|id| Event { id }));
+/// let mut stream = tonic_mock::streaming_request_from_stream(events).into_inner();
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     for id in 0..3 {
+///         assert_eq!(stream.message().await.unwrap(), Some(Event { id }));
+///     }
+///     assert_eq!(stream.message().await.unwrap(), None);
+/// });
+/// ```
+pub fn streaming_request_from_stream<T, S>(stream: S) -> Request<Streaming<T>>
+where
+    T: Message + Default + Send + 'static,
+    S: Stream<Item = T> + Send + 'static,
+{
+    let body = MockBody::from_stream(stream.map(Ok));
+    let decoder: ProstDecoder<T> = ProstDecoder::new();
+    let stream = Streaming::new_request(decoder, body, None, None);
+
+    Request::new(stream)
+}
+
+/// Generate streaming request that calls `on_drop` once the underlying body is dropped
+///
+/// Useful for checking whether a service consumed or released its input stream.
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use std::sync::{
+///     atomic::{AtomicBool, Ordering},
+///     Arc,
+/// };
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let dropped = Arc::new(AtomicBool::new(false));
+/// let flag = dropped.clone();
+/// let events = vec![Event { id: 0 }, Event { id: 1 }, Event { id: 2 }];
+/// let request = tonic_mock::streaming_request_with_drop_callback(events, move || {
+///     flag.store(true, Ordering::SeqCst)
+/// });
+/// let mut stream = request.into_inner();
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     // stop after the first message, like a short-circuiting service
+///     assert_eq!(stream.message().await.unwrap(), Some(Event { id: 0 }));
+/// });
+/// assert!(!dropped.load(Ordering::SeqCst));
+/// drop(stream);
+/// assert!(dropped.load(Ordering::SeqCst));
+/// ```
+pub fn streaming_request_with_drop_callback<T, F>(
+    messages: Vec<T>,
+    on_drop: F,
+) -> Request<Streaming<T>>
+where
+    T: Message + Default + Send + 'static,
+    F: FnOnce() + Send + 'static,
+{
+    let body = MockBody::new(messages).with_drop_callback(on_drop);
+    let decoder: ProstDecoder<T> = ProstDecoder::new();
+    let stream = Streaming::new_request(decoder, body, None, None);
+
+    Request::new(stream)
+}
+
+/// Generate streaming request advertising the given encodings in `grpc-accept-encoding`
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic::codec::CompressionEncoding;
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let request = tonic_mock::streaming_request_with_compression_hint(
+///     vec![Event { id: 1 }],
+///     &[CompressionEncoding::Gzip],
+/// );
+/// assert_eq!(request.metadata().get("grpc-accept-encoding").unwrap(), "gzip");
+/// ```
+#[cfg(feature = "compression")]
+pub fn streaming_request_with_compression_hint<T>(
+    messages: Vec<T>,
+    encodings: &[CompressionEncoding],
+) -> Request<Streaming<T>>
+where
+    T: Message + Default + 'static,
+{
+    let mut request = streaming_request(messages);
+    let value = encodings
+        .iter()
+        .map(|encoding| encoding.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    request.metadata_mut().insert(
+        "grpc-accept-encoding",
+        value.parse().expect("invalid grpc-accept-encoding value"),
+    );
+    request
+}
+
 /// a simple wrapper to process and validate streaming response
 ///
 /// Usage:
@@ -82,11 +270,57 @@ pub async fn process_streaming_response<T, F>(response: StreamResponse<T>, f: F)
 where
     T: Message + Default + 'static,
     F: Fn(Result<T, Status>, usize),
+{
+    process_streaming_response_until(response, |v, i| {
+        f(v, i);
+        true
+    })
+    .await
+}
+
+/// process a streaming response until `f` returns `false`, then drop the rest of the stream
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 1 };
+///     yield ResponsePush { code: 2 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// // stop at the first sentinel value
+/// let mut seen = Vec::new();
+/// rt.block_on(async {
+///     tonic_mock::process_streaming_response_until(response, |msg, _| {
+///         let code = msg.unwrap().code;
+///         seen.push(code);
+///         code != 1
+///     })
+///     .await;
+/// });
+/// assert_eq!(seen, vec![0, 1]);
+/// ```
+pub async fn process_streaming_response_until<T, F>(response: StreamResponse<T>, mut f: F)
+where
+    T: Message + Default + 'static,
+    F: FnMut(Result<T, Status>, usize) -> bool,
 {
     let mut i: usize = 0;
     let mut messages = response.into_inner();
     while let Some(v) = messages.next().await {
-        f(v, i);
+        if !f(v, i) {
+            break;
+        }
         i += 1;
     }
 }
@@ -132,3 +366,1457 @@ where
     }
     result
 }
+
+/// convert a streaming response to a Vec, recording the time each item was received
+///
+/// Usage:
+/// ```
+/// use std::time::{Duration, Instant};
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 1 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let start = Instant::now();
+/// let result = rt.block_on(async { tonic_mock::stream_to_vec_timed(response).await });
+/// assert_eq!(result.len(), 2);
+/// tonic_mock::assert_latencies_within(&result, start, Duration::from_secs(1));
+/// ```
+pub async fn stream_to_vec_timed<T>(
+    response: StreamResponse<T>,
+) -> Vec<(Result<T, Status>, Instant)>
+where
+    T: Message + Default + 'static,
+{
+    let mut result = Vec::new();
+    let mut messages = response.into_inner();
+    while let Some(v) = messages.next().await {
+        result.push((v, Instant::now()))
+    }
+    result
+}
+
+/// assert that every timestamp in `results` falls within `max` after `start`
+///
+/// Panics with the index and the observed latency of the first item outside the window.
+pub fn assert_latencies_within<T>(results: &[(T, Instant)], start: Instant, max: Duration) {
+    for (i, (_, received_at)) in results.iter().enumerate() {
+        assert!(
+            *received_at >= start,
+            "item {} was received before the start instant",
+            i
+        );
+        let latency = received_at.duration_since(start);
+        assert!(
+            latency <= max,
+            "item {} was received after {:?}, expected within {:?}",
+            i,
+            latency,
+            max
+        );
+    }
+}
+
+/// process a streaming response and report progress after each item
+///
+/// `progress` receives `(received_count, total_if_known)`. The total is only known when the
+/// underlying stream reports an exact [`Stream::size_hint`] before processing starts.
+///
+/// Usage:
+/// ```
+/// use std::sync::Mutex;
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 1 };
+///     yield ResponsePush { code: 2 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let reported = Mutex::new(Vec::new());
+/// rt.block_on(async {
+///     tonic_mock::process_streaming_response_with_progress(
+///         response,
+///         |msg, i| assert_eq!(msg.unwrap().code, i as i32),
+///         |received, total| reported.lock().unwrap().push((received, total)),
+///     )
+///     .await;
+/// });
+/// assert_eq!(reported.into_inner().unwrap(), vec![(1, None), (2, None), (3, None)]);
+/// ```
+pub async fn process_streaming_response_with_progress<T, F, P>(
+    response: StreamResponse<T>,
+    f: F,
+    progress: P,
+) where
+    T: Message + Default + 'static,
+    F: Fn(Result<T, Status>, usize),
+    P: Fn(usize, Option<usize>),
+{
+    let mut i: usize = 0;
+    let mut messages = response.into_inner();
+    let total = match messages.size_hint() {
+        (lower, Some(upper)) if lower == upper => Some(upper),
+        _ => None,
+    };
+    while let Some(v) = messages.next().await {
+        f(v, i);
+        i += 1;
+        progress(i, total);
+    }
+}
+
+/// convert at most `max` items of a streaming response to a Vec, then drop the stream
+///
+/// Dropping the stream signals cancellation to the producer, so this is safe to use with
+/// unbounded streams.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// // an endless stream
+/// let output = async_stream::try_stream! {
+///     let mut code = 0;
+///     loop {
+///         yield ResponsePush { code };
+///         code += 1;
+///     }
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let result = rt.block_on(async { tonic_mock::stream_to_vec_with_max(response, 10).await });
+/// assert_eq!(result.len(), 10);
+/// assert_eq!(result[9].as_ref().unwrap().code, 9);
+/// ```
+pub async fn stream_to_vec_with_max<T>(
+    response: StreamResponse<T>,
+    max: usize,
+) -> Vec<Result<T, Status>>
+where
+    T: Message + Default + 'static,
+{
+    let mut result = Vec::new();
+    let mut messages = response.into_inner();
+    while result.len() < max {
+        match messages.next().await {
+            Some(v) => result.push(v),
+            None => break,
+        }
+    }
+    result
+}
+
+/// like [`stream_to_vec_with_max`], but also stops when waiting for the next item exceeds `timeout`
+///
+/// Items collected before the timeout are returned; the stream is dropped either way.
+///
+/// Usage:
+/// ```
+/// use std::time::Duration;
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// // a stream that stalls after the first two items
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 1 };
+///     tokio::time::sleep(Duration::from_secs(10)).await;
+///     yield ResponsePush { code: 2 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let result = rt.block_on(async {
+///     tonic_mock::stream_to_vec_with_max_and_timeout(response, 10, Duration::from_millis(50)).await
+/// });
+/// assert_eq!(result.len(), 2);
+/// ```
+pub async fn stream_to_vec_with_max_and_timeout<T>(
+    response: StreamResponse<T>,
+    max: usize,
+    timeout: Duration,
+) -> Vec<Result<T, Status>>
+where
+    T: Message + Default + 'static,
+{
+    let mut result = Vec::new();
+    let mut messages = response.into_inner();
+    while result.len() < max {
+        match tokio::time::timeout(timeout, messages.next()).await {
+            Ok(Some(v)) => result.push(v),
+            Ok(None) | Err(_) => break,
+        }
+    }
+    result
+}
+
+/// process a streaming response, waiting for each callback future before pulling the next item
+///
+/// This simulates a slow consumer: `process_fn` can e.g. `tokio::time::sleep` to pace reads and
+/// exercise the server's backpressure handling.
+///
+/// Usage:
+/// ```
+/// use std::time::Duration;
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 1 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// rt.block_on(async {
+///     tonic_mock::process_streaming_response_with_backpressure(response, |msg, i| async move {
+///         tokio::time::sleep(Duration::from_millis(10)).await;
+///         assert_eq!(msg.unwrap().code, i as i32);
+///     })
+///     .await;
+/// });
+/// ```
+pub async fn process_streaming_response_with_backpressure<T, F, Fut>(
+    response: StreamResponse<T>,
+    process_fn: F,
+) where
+    T: Message + Default + 'static,
+    F: Fn(Result<T, Status>, usize) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut i: usize = 0;
+    let mut messages = response.into_inner();
+    while let Some(v) = messages.next().await {
+        process_fn(v, i).await;
+        i += 1;
+    }
+}
+
+/// merge several streaming responses into one interleaved stream
+///
+/// Items are yielded as soon as any input produces them; the merged stream ends once every
+/// input is exhausted.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let first = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 1 };
+/// };
+/// let second = async_stream::try_stream! {
+///     yield ResponsePush { code: 2 };
+/// };
+/// let responses = vec![
+///     Response::new(Box::pin(first) as tonic_mock::StreamResponseInner<ResponsePush>),
+///     Response::new(Box::pin(second) as tonic_mock::StreamResponseInner<ResponsePush>),
+/// ];
+///
+/// let merged = Response::new(tonic_mock::stream_merge(responses));
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// let mut codes: Vec<i32> = rt
+///     .block_on(async { tonic_mock::stream_to_vec(merged).await })
+///     .into_iter()
+///     .map(|v| v.unwrap().code)
+///     .collect();
+/// codes.sort();
+/// assert_eq!(codes, vec![0, 1, 2]);
+/// ```
+pub fn stream_merge<T>(responses: Vec<StreamResponse<T>>) -> StreamResponseInner<T>
+where
+    T: Message + Default + Send + 'static,
+{
+    let streams = responses.into_iter().map(Response::into_inner);
+    Box::pin(stream::select_all(streams))
+}
+
+/// merge several individually ordered streaming responses, keeping the output ordered by `key_fn`
+///
+/// Each input is expected to be sorted by `key_fn` already; the result is a k-way merge of them.
+/// Errors are yielded as soon as they are seen at the head of an input.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let odd = async_stream::try_stream! {
+///     yield ResponsePush { code: 1 };
+///     yield ResponsePush { code: 3 };
+/// };
+/// let even = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 2 };
+///     yield ResponsePush { code: 4 };
+/// };
+/// let responses = vec![
+///     Response::new(Box::pin(odd) as tonic_mock::StreamResponseInner<ResponsePush>),
+///     Response::new(Box::pin(even) as tonic_mock::StreamResponseInner<ResponsePush>),
+/// ];
+///
+/// let merged = Response::new(tonic_mock::stream_merge_ordered(responses, |r| r.code));
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// let codes: Vec<i32> = rt
+///     .block_on(async { tonic_mock::stream_to_vec(merged).await })
+///     .into_iter()
+///     .map(|v| v.unwrap().code)
+///     .collect();
+/// assert_eq!(codes, vec![0, 1, 2, 3, 4]);
+/// ```
+pub fn stream_merge_ordered<T, K, F>(
+    responses: Vec<StreamResponse<T>>,
+    key_fn: F,
+) -> StreamResponseInner<T>
+where
+    T: Message + Default + Send + 'static,
+    K: Ord,
+    F: Fn(&T) -> K + Send + Sync + 'static,
+{
+    let streams: Vec<_> = responses
+        .into_iter()
+        .map(|r| r.into_inner().fuse())
+        .collect();
+    let heads: Vec<Option<Result<T, Status>>> = streams.iter().map(|_| None).collect();
+
+    let output = stream::unfold(
+        (streams, heads, key_fn),
+        |(mut streams, mut heads, key_fn)| async move {
+            for (stream, head) in streams.iter_mut().zip(heads.iter_mut()) {
+                if head.is_none() {
+                    *head = stream.next().await;
+                }
+            }
+
+            let next = heads
+                .iter()
+                .position(|head| matches!(head, Some(Err(_))))
+                .or_else(|| {
+                    heads
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, head)| match head {
+                            Some(Ok(v)) => Some((i, key_fn(v))),
+                            _ => None,
+                        })
+                        .min_by(|a, b| a.1.cmp(&b.1))
+                        .map(|(i, _)| i)
+                })?;
+            let item = heads[next].take()?;
+            Some((item, (streams, heads, key_fn)))
+        },
+    );
+    Box::pin(output)
+}
+
+/// process a streaming response, projecting each successful item with `transform` first
+///
+/// `Err` items are passed to `process` unchanged.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 1 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// rt.block_on(async {
+///     tonic_mock::process_streaming_response_with_map(
+///         response,
+///         |msg| msg.code,
+///         |code, i| assert_eq!(code.unwrap(), i as i32),
+///     )
+///     .await;
+/// });
+/// ```
+pub async fn process_streaming_response_with_map<T, U, F, G>(
+    response: StreamResponse<T>,
+    transform: F,
+    process: G,
+) where
+    T: Message + Default + 'static,
+    F: Fn(T) -> U,
+    G: Fn(Result<U, Status>, usize),
+{
+    let mut i: usize = 0;
+    let mut messages = response.into_inner();
+    while let Some(v) = messages.next().await {
+        process(v.map(&transform), i);
+        i += 1;
+    }
+}
+
+/// pass a streaming response through unchanged, printing each item to stderr
+///
+/// Printing only happens when the `TONIC_MOCK_STREAM_DEBUG` environment variable is set to `1`,
+/// so the call can stay in place while writing tests and be switched on in CI when needed.
+/// Each line shows the label, the item index and the item's debug representation.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let response = tonic_mock::stream_debug_print(response, "push");
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// let result = rt.block_on(async { tonic_mock::stream_to_vec(response).await });
+/// assert_eq!(result[0].as_ref().unwrap().code, 0);
+/// ```
+pub fn stream_debug_print<T>(response: StreamResponse<T>, label: &str) -> StreamResponse<T>
+where
+    T: Debug + Message + Default + Clone + Send + 'static,
+{
+    let enabled = std::env::var("TONIC_MOCK_STREAM_DEBUG").is_ok_and(|v| v == "1");
+    let label = label.to_string();
+    stream_inspect(response, move |v, i| {
+        if enabled {
+            eprintln!("[{}] #{}: {:?}", label, i, v);
+        }
+    })
+}
+
+/// call `f` with a reference to each item and its index as it passes through unchanged
+///
+/// Usage:
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 1 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+///
+/// let seen = Arc::new(Mutex::new(Vec::new()));
+/// let log = seen.clone();
+/// let response = tonic_mock::stream_inspect(response, move |v, i| {
+///     log.lock().unwrap().push((i, v.as_ref().unwrap().code));
+/// });
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// let result = rt.block_on(async { tonic_mock::stream_to_vec(response).await });
+/// assert_eq!(result.len(), 2);
+/// assert_eq!(*seen.lock().unwrap(), vec![(0, 0), (1, 1)]);
+/// ```
+pub fn stream_inspect<T, F>(response: StreamResponse<T>, f: F) -> StreamResponse<T>
+where
+    T: 'static,
+    F: Fn(&Result<T, Status>, usize) + Send + Sync + 'static,
+{
+    let (metadata, messages, extensions) = response.into_parts();
+    let output = messages.enumerate().map(move |(i, v)| {
+        f(&v, i);
+        v
+    });
+    Response::from_parts(metadata, Box::pin(output), extensions)
+}
+
+/// feed every item of a streaming response into an mpsc channel
+///
+/// Returns the number of items sent. Stops early if the receiver was dropped.
+///
+/// Usage:
+/// ```
+/// use tokio::sync::mpsc;
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 1 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// rt.block_on(async {
+///     let (tx, mut rx) = mpsc::channel(4);
+///     let sent = tonic_mock::stream_to_channel(response, tx).await;
+///     assert_eq!(sent, 2);
+///     assert_eq!(rx.recv().await.unwrap().unwrap().code, 0);
+///     assert_eq!(rx.recv().await.unwrap().unwrap().code, 1);
+///     assert!(rx.recv().await.is_none());
+/// });
+/// ```
+pub async fn stream_to_channel<T>(
+    response: StreamResponse<T>,
+    sender: mpsc::Sender<Result<T, Status>>,
+) -> usize
+where
+    T: Message + Default + Send + 'static,
+{
+    let mut sent: usize = 0;
+    let mut messages = response.into_inner();
+    while let Some(v) = messages.next().await {
+        if sender.send(v).await.is_err() {
+            break;
+        }
+        sent += 1;
+    }
+    sent
+}
+
+/// feed every item of a streaming response into a broadcast channel
+///
+/// Returns the number of items sent. Stops early once there are no receivers left.
+///
+/// Usage:
+/// ```
+/// use tokio::sync::broadcast;
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// rt.block_on(async {
+///     let (tx, mut rx1) = broadcast::channel(4);
+///     let mut rx2 = tx.subscribe();
+///     assert_eq!(tonic_mock::stream_to_broadcast_channel(response, tx).await, 1);
+///     assert_eq!(rx1.recv().await.unwrap().unwrap().code, 0);
+///     assert_eq!(rx2.recv().await.unwrap().unwrap().code, 0);
+/// });
+/// ```
+pub async fn stream_to_broadcast_channel<T>(
+    response: StreamResponse<T>,
+    sender: broadcast::Sender<Result<T, Status>>,
+) -> usize
+where
+    T: Message + Default + Clone + Send + 'static,
+{
+    let mut sent: usize = 0;
+    let mut messages = response.into_inner();
+    while let Some(v) = messages.next().await {
+        if sender.send(v).is_err() {
+            break;
+        }
+        sent += 1;
+    }
+    sent
+}
+
+/// collect the successful items of a streaming response into a HashMap keyed by `key_fn`
+///
+/// Errors are dropped. When a key appears more than once the last item wins; use
+/// [`stream_to_hashmap_with_conflict_handler`] to control that.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct User {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+///     #[prost(string, tag = "2")]
+///     pub name: String,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield User { id: 1, name: "alice".into() };
+///     yield User { id: 2, name: "bob".into() };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<User>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let users = rt.block_on(async { tonic_mock::stream_to_hashmap(response, |u| u.id).await });
+/// assert_eq!(users[&2].name, "bob");
+/// ```
+pub async fn stream_to_hashmap<T, K, F>(response: StreamResponse<T>, key_fn: F) -> HashMap<K, T>
+where
+    T: Message + Default + 'static,
+    K: Eq + Hash,
+    F: Fn(&T) -> K,
+{
+    stream_to_hashmap_with_conflict_handler(response, key_fn, |_, new| new).await
+}
+
+/// like [`stream_to_hashmap`], but resolves duplicate keys with `on_conflict(existing, new)`
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct Score {
+///     #[prost(string, tag = "1")]
+///     pub player: String,
+///     #[prost(int32, tag = "2")]
+///     pub points: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield Score { player: "alice".into(), points: 3 };
+///     yield Score { player: "alice".into(), points: 4 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<Score>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let totals = rt.block_on(async {
+///     tonic_mock::stream_to_hashmap_with_conflict_handler(
+///         response,
+///         |s| s.player.clone(),
+///         |existing, new| Score { points: existing.points + new.points, ..new },
+///     )
+///     .await
+/// });
+/// assert_eq!(totals["alice"].points, 7);
+/// ```
+pub async fn stream_to_hashmap_with_conflict_handler<T, K, F, C>(
+    response: StreamResponse<T>,
+    key_fn: F,
+    on_conflict: C,
+) -> HashMap<K, T>
+where
+    T: Message + Default + 'static,
+    K: Eq + Hash,
+    F: Fn(&T) -> K,
+    C: Fn(T, T) -> T,
+{
+    let mut result = HashMap::new();
+    let mut messages = response.into_inner();
+    while let Some(v) = messages.next().await {
+        if let Ok(item) = v {
+            let key = key_fn(&item);
+            let item = match result.remove(&key) {
+                Some(existing) => on_conflict(existing, item),
+                None => item,
+            };
+            result.insert(key, item);
+        }
+    }
+    result
+}
+
+/// consume a streaming response and return only the errors it produced
+///
+/// Usage:
+/// ```
+/// use tonic::{Code, Response, Status};
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = futures::stream::iter(vec![
+///     Ok(ResponsePush { code: 0 }),
+///     Err(Status::unavailable("retry")),
+///     Ok(ResponsePush { code: 1 }),
+/// ]);
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let errors = rt.block_on(async { tonic_mock::process_streaming_response_collect_errors(response).await });
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].code(), Code::Unavailable);
+/// ```
+pub async fn process_streaming_response_collect_errors<T>(
+    response: StreamResponse<T>,
+) -> Vec<Status>
+where
+    T: Message + Default + 'static,
+{
+    let mut errors = Vec::new();
+    let mut messages = response.into_inner();
+    while let Some(v) = messages.next().await {
+        if let Err(status) = v {
+            errors.push(status);
+        }
+    }
+    errors
+}
+
+/// consume a streaming response and panic with the first error found, if any
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(tonic_mock::process_streaming_response_assert_no_errors(response));
+/// ```
+pub async fn process_streaming_response_assert_no_errors<T>(response: StreamResponse<T>)
+where
+    T: Message + Default + 'static,
+{
+    let errors = process_streaming_response_collect_errors(response).await;
+    if let Some(status) = errors.first() {
+        panic!(
+            "stream produced {} error(s), first: {:?}: {}",
+            errors.len(),
+            status.code(),
+            status.message()
+        );
+    }
+}
+
+/// convert a streaming response to a Vec, also returning the response's initial metadata
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+/// };
+/// let mut response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// response.metadata_mut().insert("x-rate-limit", "100".parse().unwrap());
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let (result, metadata) = rt.block_on(async { tonic_mock::stream_to_vec_with_context(response).await });
+/// assert_eq!(result.len(), 1);
+/// assert_eq!(metadata.get("x-rate-limit").unwrap(), "100");
+/// ```
+pub async fn stream_to_vec_with_context<T>(
+    response: StreamResponse<T>,
+) -> (Vec<Result<T, Status>>, MetadataMap)
+where
+    T: Message + Default + 'static,
+{
+    let (metadata, mut messages, _) = response.into_parts();
+    let mut result = Vec::new();
+    while let Some(v) = messages.next().await {
+        result.push(v)
+    }
+    (result, metadata)
+}
+
+/// process a streaming response that interleaves several sessions, tracking a per-session index
+///
+/// `f` receives `(message, global_index, session_index)`, where the session of an item is
+/// given by `session_key_fn` and each session counts from 0. Errors belong to no session and
+/// are reported with their global index as session index.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct Chunk {
+///     #[prost(string, tag = "1")]
+///     pub session: String,
+///     #[prost(int32, tag = "2")]
+///     pub seq: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield Chunk { session: "a".into(), seq: 0 };
+///     yield Chunk { session: "b".into(), seq: 0 };
+///     yield Chunk { session: "a".into(), seq: 1 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<Chunk>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// rt.block_on(async {
+///     tonic_mock::process_streaming_response_with_session(
+///         response,
+///         |chunk| chunk.session.clone(),
+///         |msg, _global, session_index| assert_eq!(msg.unwrap().seq, session_index as i32),
+///     )
+///     .await;
+/// });
+/// ```
+pub async fn process_streaming_response_with_session<T, K, S, F>(
+    response: StreamResponse<T>,
+    session_key_fn: S,
+    f: F,
+) where
+    T: Message + Default + 'static,
+    K: Eq + Hash,
+    S: Fn(&T) -> K,
+    F: Fn(Result<T, Status>, usize, usize),
+{
+    let mut i: usize = 0;
+    let mut sessions: HashMap<K, usize> = HashMap::new();
+    let mut messages = response.into_inner();
+    while let Some(v) = messages.next().await {
+        let session_index = match &v {
+            Ok(msg) => {
+                let counter = sessions.entry(session_key_fn(msg)).or_insert(0);
+                *counter += 1;
+                *counter - 1
+            }
+            Err(_) => i,
+        };
+        f(v, i, session_index);
+        i += 1;
+    }
+}
+
+/// collect items until one matches `predicate` (inclusive), then drop the rest of the stream
+///
+/// Errors are collected and never match. If nothing matches the whole stream is consumed.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: -1 }; // "done" sentinel
+///     yield ResponsePush { code: 2 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let result = rt.block_on(async { tonic_mock::stream_take_until(response, |r| r.code < 0).await });
+/// assert_eq!(result.len(), 2);
+/// ```
+pub async fn stream_take_until<T, F>(
+    response: StreamResponse<T>,
+    predicate: F,
+) -> Vec<Result<T, Status>>
+where
+    T: Message + Default + 'static,
+    F: Fn(&T) -> bool,
+{
+    let mut result = Vec::new();
+    let mut messages = response.into_inner();
+    while let Some(v) = messages.next().await {
+        let done = matches!(&v, Ok(msg) if predicate(msg));
+        result.push(v);
+        if done {
+            break;
+        }
+    }
+    result
+}
+
+/// collect items while they satisfy `predicate`, then drop the rest of the stream
+///
+/// The first failing item is not included. Errors are collected and do not stop collection.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 1 };
+///     yield ResponsePush { code: 5 };
+///     yield ResponsePush { code: 2 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let result = rt.block_on(async { tonic_mock::stream_take_while(response, |r| r.code < 5).await });
+/// assert_eq!(result.len(), 2);
+/// ```
+pub async fn stream_take_while<T, F>(
+    response: StreamResponse<T>,
+    predicate: F,
+) -> Vec<Result<T, Status>>
+where
+    T: Message + Default + 'static,
+    F: Fn(&T) -> bool,
+{
+    let mut result = Vec::new();
+    let mut messages = response.into_inner();
+    while let Some(v) = messages.next().await {
+        if matches!(&v, Ok(msg) if !predicate(msg)) {
+            break;
+        }
+        result.push(v);
+    }
+    result
+}
+
+/// a blocking iterator over a streaming response, for use outside of async code
+///
+/// Each call to `next` blocks on the given runtime handle, so it must not be used from within
+/// an async context. See [`into_blocking_iter`].
+pub struct BlockingStreamIter<T> {
+    inner: StreamResponseInner<T>,
+    rt: tokio::runtime::Handle,
+}
+
+impl<T> Iterator for BlockingStreamIter<T> {
+    type Item = Result<T, Status>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let inner = &mut self.inner;
+        self.rt.block_on(inner.next())
+    }
+}
+
+/// turn a streaming response into a blocking iterator driven by `rt`
+///
+/// This lets synchronous `#[test]` functions use iterator combinators on a streaming response.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 1 };
+///     yield ResponsePush { code: 2 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let odd: Vec<i32> = tonic_mock::into_blocking_iter(response, rt.handle().clone())
+///     .map(|v| v.unwrap().code)
+///     .filter(|code| code % 2 == 1)
+///     .collect();
+/// assert_eq!(odd, vec![1]);
+/// ```
+pub fn into_blocking_iter<T>(
+    response: StreamResponse<T>,
+    rt: tokio::runtime::Handle,
+) -> BlockingStreamIter<T> {
+    BlockingStreamIter {
+        inner: response.into_inner(),
+        rt,
+    }
+}
+
+/// expand each successful item of a streaming response into several items and collect them
+///
+/// Errors are passed through unchanged.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct Batch {
+///     #[prost(int32, repeated, tag = "1")]
+///     pub ids: Vec<i32>,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield Batch { ids: vec![1, 2] };
+///     yield Batch { ids: vec![3] };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<Batch>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let ids: Vec<i32> = rt
+///     .block_on(async { tonic_mock::stream_flat_map(response, |batch| batch.ids).await })
+///     .into_iter()
+///     .map(Result::unwrap)
+///     .collect();
+/// assert_eq!(ids, vec![1, 2, 3]);
+/// ```
+pub async fn stream_flat_map<T, U, F>(response: StreamResponse<T>, f: F) -> Vec<Result<U, Status>>
+where
+    T: Message + Default + 'static,
+    F: Fn(T) -> Vec<U>,
+{
+    let mut result = Vec::new();
+    let mut messages = response.into_inner();
+    while let Some(v) = messages.next().await {
+        match v {
+            Ok(msg) => result.extend(f(msg).into_iter().map(Ok)),
+            Err(status) => result.push(Err(status)),
+        }
+    }
+    result
+}
+
+/// like [`stream_flat_map`], but with an async expansion function
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct Batch {
+///     #[prost(int32, repeated, tag = "1")]
+///     pub ids: Vec<i32>,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield Batch { ids: vec![1, 2] };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<Batch>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let result = rt.block_on(async {
+///     tonic_mock::stream_flat_map_async(response, |batch| async move { batch.ids }).await
+/// });
+/// assert_eq!(result.len(), 2);
+/// ```
+pub async fn stream_flat_map_async<T, U, F, Fut>(
+    response: StreamResponse<T>,
+    f: F,
+) -> Vec<Result<U, Status>>
+where
+    T: Message + Default + 'static,
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = Vec<U>>,
+{
+    let mut result = Vec::new();
+    let mut messages = response.into_inner();
+    while let Some(v) = messages.next().await {
+        match v {
+            Ok(msg) => result.extend(f(msg).await.into_iter().map(Ok)),
+            Err(status) => result.push(Err(status)),
+        }
+    }
+    result
+}
+
+/// call a streaming service and process its response, re-calling it on `Unavailable` errors
+///
+/// When the call or its stream fails with `Code::Unavailable`, the service is called again with
+/// a fresh request from `request_fn`, up to `max_retries` times. The failed item is not passed to
+/// `f`; once retries are exhausted it is. The index given to `f` restarts at 0 with each attempt.
+/// Returns the number of retries that happened.
+///
+/// This is a testing utility for retry-aware consumers, not production retry logic.
+///
+/// Usage:
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use tonic::{Request, Response, Status};
+/// use tonic_mock::{StreamResponse, StreamResponseInner};
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// // fails mid-stream on the first call, succeeds afterwards
+/// let calls = AtomicUsize::new(0);
+/// let service = |_req: Request<()>| {
+///     let attempt = calls.fetch_add(1, Ordering::SeqCst);
+///     async move {
+///         let mut items = vec![Ok(ResponsePush { code: 0 })];
+///         if attempt == 0 {
+///             items.push(Err(Status::unavailable("try again")));
+///         } else {
+///             items.push(Ok(ResponsePush { code: 1 }));
+///         }
+///         let output = futures::stream::iter(items);
+///         Ok::<StreamResponse<ResponsePush>, Status>(Response::new(
+///             Box::pin(output) as StreamResponseInner<ResponsePush>,
+///         ))
+///     }
+/// };
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// let retries = rt.block_on(tonic_mock::process_streaming_response_with_retry(
+///     service,
+///     || Request::new(()),
+///     |msg, i| assert_eq!(msg.unwrap().code, i as i32),
+///     3,
+/// ));
+/// assert_eq!(retries, 1);
+/// ```
+pub async fn process_streaming_response_with_retry<Req, T, F, SF, SFut, RF>(
+    service_fn: SF,
+    request_fn: RF,
+    f: F,
+    max_retries: usize,
+) -> usize
+where
+    T: Message + Default + 'static,
+    F: Fn(Result<T, Status>, usize),
+    SF: Fn(Request<Req>) -> SFut,
+    SFut: Future<Output = Result<StreamResponse<T>, Status>>,
+    RF: Fn() -> Request<Req>,
+{
+    let mut retries: usize = 0;
+    'attempts: loop {
+        let can_retry = retries < max_retries;
+        let mut messages = match service_fn(request_fn()).await {
+            Ok(response) => response.into_inner(),
+            Err(status) if can_retry && status.code() == Code::Unavailable => {
+                retries += 1;
+                continue;
+            }
+            Err(status) => {
+                f(Err(status), 0);
+                return retries;
+            }
+        };
+
+        let mut i: usize = 0;
+        while let Some(v) = messages.next().await {
+            if can_retry && matches!(&v, Err(status) if status.code() == Code::Unavailable) {
+                retries += 1;
+                continue 'attempts;
+            }
+            f(v, i);
+            i += 1;
+        }
+        return retries;
+    }
+}
+
+/// Outcome of [`process_streaming_response_with_cancel`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CancellationResult {
+    pub items_processed: usize,
+    pub cancelled: bool,
+}
+
+/// process a streaming response until it ends or `cancel` fires, then drop the stream
+///
+/// If the sender side of `cancel` is dropped without sending, the stream is processed to the
+/// end.
+///
+/// Usage:
+/// ```
+/// use futures::{stream, StreamExt};
+/// use tonic::{Response, Status};
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// // yields one item, then hangs like a server that stopped sending
+/// let output = stream::iter(vec![Ok::<_, Status>(ResponsePush { code: 0 })]).chain(stream::pending());
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel();
+/// cancel_tx.send(()).unwrap();
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// let result = rt.block_on(async {
+///     tonic_mock::process_streaming_response_with_cancel(response, cancel_rx, |msg, i| {
+///         assert_eq!(msg.unwrap().code, i as i32);
+///     })
+///     .await
+/// });
+/// assert_eq!(result.items_processed, 1);
+/// assert!(result.cancelled);
+/// ```
+pub async fn process_streaming_response_with_cancel<T, F>(
+    response: StreamResponse<T>,
+    cancel: oneshot::Receiver<()>,
+    f: F,
+) -> CancellationResult
+where
+    T: Message + Default + 'static,
+    F: Fn(Result<T, Status>, usize),
+{
+    let mut messages = response.into_inner();
+    let mut cancel = Some(cancel);
+    let mut items_processed: usize = 0;
+    loop {
+        let next = match cancel.as_mut() {
+            Some(rx) => match future::select(messages.next(), rx).await {
+                Either::Left((next, _)) => next,
+                Either::Right((Ok(()), _)) => {
+                    return CancellationResult {
+                        items_processed,
+                        cancelled: true,
+                    }
+                }
+                Either::Right((Err(_), _)) => {
+                    cancel = None;
+                    continue;
+                }
+            },
+            None => messages.next().await,
+        };
+        match next {
+            Some(v) => {
+                f(v, items_processed);
+                items_processed += 1;
+            }
+            None => {
+                return CancellationResult {
+                    items_processed,
+                    cancelled: false,
+                }
+            }
+        }
+    }
+}
+
+/// assert a streaming response yields exactly `expected`, item by item
+///
+/// Panics on the first mismatch or error, naming the index, or if the lengths differ.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 1 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let expected = vec![ResponsePush { code: 0 }, ResponsePush { code: 1 }];
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     tonic_mock::process_streaming_response_zip_with_expected(response, expected).await;
+/// });
+/// ```
+pub async fn process_streaming_response_zip_with_expected<T>(
+    response: StreamResponse<T>,
+    expected: Vec<T>,
+) where
+    T: Debug + PartialEq,
+{
+    process_streaming_response_zip_with_expected_by(response, expected, |a, e| a == e).await
+}
+
+/// assert a streaming response matches `expected` item by item using `comparator`
+///
+/// `comparator` receives `(actual, expected)` and returns whether they match.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+///     #[prost(string, tag = "2")]
+///     pub trace_id: String,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0, trace_id: "a1".into() };
+///     yield ResponsePush { code: 1, trace_id: "b2".into() };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+/// let expected = vec![
+///     ResponsePush { code: 0, trace_id: String::new() },
+///     ResponsePush { code: 1, trace_id: String::new() },
+/// ];
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     // ignore the generated trace ids
+///     tonic_mock::process_streaming_response_zip_with_expected_by(response, expected, |a, e| {
+///         a.code == e.code
+///     })
+///     .await;
+/// });
+/// ```
+pub async fn process_streaming_response_zip_with_expected_by<T, C>(
+    response: StreamResponse<T>,
+    expected: Vec<T>,
+    comparator: C,
+) where
+    T: Debug,
+    C: Fn(&T, &T) -> bool,
+{
+    let mut messages = response.into_inner();
+    let mut remaining = expected.into_iter();
+    let mut i: usize = 0;
+    loop {
+        match (messages.next().await, remaining.next()) {
+            (None, None) => return,
+            (Some(Ok(actual)), Some(expected)) => assert!(
+                comparator(&actual, &expected),
+                "item {} differs: actual {:?}, expected {:?}",
+                i,
+                actual,
+                expected
+            ),
+            (Some(Err(status)), Some(expected)) => panic!(
+                "item {} is an error {:?}, expected {:?}",
+                i, status, expected
+            ),
+            (Some(actual), None) => panic!(
+                "stream yielded more than the {} expected items, next: {:?}",
+                i, actual
+            ),
+            (None, Some(expected)) => panic!(
+                "stream ended after {} items, expected {} more starting with {:?}",
+                i,
+                remaining.len() + 1,
+                expected
+            ),
+        }
+        i += 1;
+    }
+}
+
+/// process a streaming response, collecting every failure reported by `f` instead of panicking
+///
+/// Returns `Err` with all failure messages, in stream order, if any item failed.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = async_stream::try_stream! {
+///     yield ResponsePush { code: 0 };
+///     yield ResponsePush { code: 5 };
+///     yield ResponsePush { code: 7 };
+/// };
+/// let response = Response::new(Box::pin(output) as tonic_mock::StreamResponseInner<ResponsePush>);
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// let result = rt.block_on(async {
+///     tonic_mock::process_streaming_response_with_assertion(response, |msg, i| {
+///         let code = msg.map_err(|e| e.to_string())?.code;
+///         if code == i as i32 {
+///             Ok(())
+///         } else {
+///             Err(format!("item {}: code {}", i, code))
+///         }
+///     })
+///     .await
+/// });
+/// assert_eq!(result.unwrap_err(), vec!["item 1: code 5", "item 2: code 7"]);
+/// ```
+pub async fn process_streaming_response_with_assertion<T, F>(
+    response: StreamResponse<T>,
+    f: F,
+) -> Result<(), Vec<String>>
+where
+    T: Message + Default + 'static,
+    F: Fn(Result<T, Status>, usize) -> Result<(), String>,
+{
+    let mut failures = Vec::new();
+    let mut i: usize = 0;
+    let mut messages = response.into_inner();
+    while let Some(v) = messages.next().await {
+        if let Err(failure) = f(v, i) {
+            failures.push(failure);
+        }
+        i += 1;
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures)
+    }
+}
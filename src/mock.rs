@@ -1,58 +1,412 @@
 use bytes::{Buf, BufMut, Bytes, BytesMut};
+#[cfg(feature = "compression")]
+use flate2::{write::GzEncoder, Compression};
+use futures::{ready, Stream, StreamExt};
 use http_body::Body;
 use prost::Message;
+#[cfg(feature = "compression")]
+use std::io::Write;
 use std::{
     collections::VecDeque,
+    fmt,
     marker::PhantomData,
     pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
     task::{Context, Poll},
 };
 
+#[cfg(feature = "compression")]
+use tonic::codec::CompressionEncoding;
 use tonic::{
     codec::{DecodeBuf, Decoder},
     Status,
 };
 
+use crate::StreamResponseInner;
+
 #[derive(Clone)]
 pub struct MockBody {
-    data: VecDeque<Bytes>,
+    data: VecDeque<Result<Bytes, Status>>,
+    trailers: Option<http::HeaderMap>,
+    on_drop: Option<Arc<DropGuard>>,
+    source: Option<Arc<Mutex<FrameStream>>>,
 }
 
+type FrameStream = Pin<Box<dyn Stream<Item = Result<Bytes, Status>> + Send>>;
+
 impl MockBody {
     pub fn new(data: Vec<impl Message>) -> Self {
-        let mut queue: VecDeque<Bytes> = VecDeque::with_capacity(16);
+        let mut queue: VecDeque<Result<Bytes, Status>> = VecDeque::with_capacity(16);
+        for msg in data {
+            let buf = Self::encode(&msg);
+            queue.push_back(Ok(buf));
+        }
+
+        MockBody {
+            data: queue,
+            trailers: None,
+            on_drop: None,
+            source: None,
+        }
+    }
+
+    /// Build a body whose frames are compressed with `encoding` and flagged as compressed.
+    ///
+    /// # Panics
+    ///
+    /// Only [`CompressionEncoding::Gzip`] is supported; any other encoding panics.
+    ///
+    /// Usage:
+    /// ```
+    /// use prost::Message;
+    /// use tonic::{codec::CompressionEncoding, Streaming};
+    /// use tonic_mock::{MockBody, ProstDecoder};
+    ///
+    /// #[derive(Clone, PartialEq, Message)]
+    /// pub struct Event {
+    ///     #[prost(string, tag = "1")]
+    ///     pub data: String,
+    /// }
+    ///
+    /// let event = Event { data: "a".repeat(100) };
+    /// let body = MockBody::new_compressed(vec![event.clone()], CompressionEncoding::Gzip);
+    /// let decoder: ProstDecoder<Event> = ProstDecoder::new();
+    /// let mut stream = Streaming::new_request(decoder, body, Some(CompressionEncoding::Gzip), None);
+    ///
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// let decoded = rt.block_on(async { stream.message().await.unwrap() });
+    /// assert_eq!(decoded, Some(event));
+    /// ```
+    #[cfg(feature = "compression")]
+    pub fn new_compressed(data: Vec<impl Message>, encoding: CompressionEncoding) -> Self {
+        let mut queue: VecDeque<Result<Bytes, Status>> = VecDeque::with_capacity(16);
         for msg in data {
-            let buf = Self::encode(msg);
-            queue.push_back(buf);
+            let buf = Self::encode_compressed(&msg, encoding);
+            queue.push_back(Ok(buf));
         }
 
-        MockBody { data: queue }
+        MockBody {
+            data: queue,
+            trailers: None,
+            on_drop: None,
+            source: None,
+        }
+    }
+
+    /// Build a body whose frame at `error_index` is replaced by `status`.
+    ///
+    /// Shorthand for [`MockBody::new`] followed by [`MockBody::with_error_at_frame`].
+    ///
+    /// Usage:
+    /// ```
+    /// use tonic::{Code, Status, Streaming};
+    /// use tonic_mock::{MockBody, ProstDecoder};
+    ///
+    /// #[derive(Clone, PartialEq, ::prost::Message)]
+    /// pub struct Event {
+    ///     #[prost(int32, tag = "1")]
+    ///     pub id: i32,
+    /// }
+    ///
+    /// let events = vec![Event { id: 0 }, Event { id: 1 }, Event { id: 2 }];
+    /// let body = MockBody::with_error_at(events, 2, Status::aborted("client went away"));
+    /// let decoder: ProstDecoder<Event> = ProstDecoder::new();
+    /// let mut stream = Streaming::new_request(decoder, body, None, None);
+    ///
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///     assert_eq!(stream.message().await.unwrap(), Some(Event { id: 0 }));
+    ///     assert_eq!(stream.message().await.unwrap(), Some(Event { id: 1 }));
+    ///     assert_eq!(stream.message().await.unwrap_err().code(), Code::Aborted);
+    /// });
+    /// ```
+    pub fn with_error_at(data: Vec<impl Message>, error_index: usize, status: Status) -> Self {
+        Self::new(data).with_error_at_frame(error_index, status)
+    }
+
+    /// Build a body that encodes the items of `stream` as they are polled.
+    ///
+    /// Clones of the returned body share the same underlying stream. [`MockBody::len`] relies on
+    /// the stream's size hint, so a stream without one counts as zero frames but not as empty.
+    ///
+    /// Usage:
+    /// ```
+    /// use tonic::Streaming;
+    /// use tonic_mock::{test_utils::create_stream_response_cyclic, MockBody, ProstDecoder};
+    ///
+    /// #[derive(Clone, PartialEq, ::prost::Message)]
+    /// pub struct Event {
+    ///     #[prost(int32, tag = "1")]
+    ///     pub id: i32,
+    /// }
+    ///
+    /// let response = create_stream_response_cyclic(vec![Event { id: 1 }], 2);
+    /// let body = MockBody::from_response_stream(response.into_inner());
+    /// assert_eq!(body.len(), 2);
+    /// assert_eq!(format!("{:?}", body), "MockBody { frames: 0, bytes: 0, source: Stream }");
+    /// let decoder: ProstDecoder<Event> = ProstDecoder::new();
+    /// let mut stream = Streaming::new_request(decoder, body, None, None);
+    ///
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///     assert_eq!(stream.message().await.unwrap(), Some(Event { id: 1 }));
+    ///     assert_eq!(stream.message().await.unwrap(), Some(Event { id: 1 }));
+    ///     assert_eq!(stream.message().await.unwrap(), None);
+    /// });
+    /// ```
+    pub fn from_response_stream<T>(stream: StreamResponseInner<T>) -> Self
+    where
+        T: Message + 'static,
+    {
+        Self::from_stream(stream)
+    }
+
+    pub(crate) fn from_stream<T, S>(stream: S) -> Self
+    where
+        T: Message,
+        S: Stream<Item = Result<T, Status>> + Send + 'static,
+    {
+        let frames = stream.map(|item| item.map(|msg| Self::encode(&msg))).fuse();
+        MockBody {
+            data: VecDeque::new(),
+            trailers: None,
+            on_drop: None,
+            source: Some(Arc::new(Mutex::new(Box::pin(frames)))),
+        }
     }
 
+    /// Replace the frame at `index` with `error`, which is returned when polling reaches it.
+    ///
+    /// An `index` past the last frame appends the error after all messages. For a body built from
+    /// a stream, the error replaces the stream item at `index`, or follows the last item if the
+    /// stream ends first.
+    ///
+    /// Usage:
+    /// ```
+    /// use prost::Message;
+    /// use tonic::{Code, Status, Streaming};
+    /// use tonic_mock::{MockBody, ProstDecoder};
+    ///
+    /// #[derive(Clone, PartialEq, Message)]
+    /// pub struct Event {
+    ///     #[prost(int32, tag = "1")]
+    ///     pub id: i32,
+    /// }
+    ///
+    /// let body = MockBody::new(vec![Event { id: 0 }, Event { id: 1 }])
+    ///     .with_error_at_frame(1, Status::data_loss("connection reset"));
+    /// let decoder: ProstDecoder<Event> = ProstDecoder::new();
+    /// let mut stream = Streaming::new_request(decoder, body, None, None);
+    ///
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///     assert_eq!(stream.message().await.unwrap(), Some(Event { id: 0 }));
+    ///     assert_eq!(stream.message().await.unwrap_err().code(), Code::DataLoss);
+    /// });
+    ///
+    /// // the same applies to a body backed by a stream
+    /// let response = tonic_mock::test_utils::create_stream_response_cyclic(vec![Event { id: 7 }], 3);
+    /// let body = MockBody::from_response_stream(response.into_inner())
+    ///     .with_error_at_frame(10, Status::aborted("stream reset"));
+    /// let decoder: ProstDecoder<Event> = ProstDecoder::new();
+    /// let mut stream = Streaming::new_request(decoder, body, None, None);
+    ///
+    /// rt.block_on(async {
+    ///     for _ in 0..3 {
+    ///         assert_eq!(stream.message().await.unwrap(), Some(Event { id: 7 }));
+    ///     }
+    ///     assert_eq!(stream.message().await.unwrap_err().code(), Code::Aborted);
+    /// });
+    /// ```
+    pub fn with_error_at_frame(mut self, index: usize, error: Status) -> Self {
+        if let Some(frame) = self.data.get_mut(index) {
+            *frame = Err(error);
+            return self;
+        }
+        match self.source.take() {
+            Some(inner) => {
+                let injected = ErrorAtFrame {
+                    inner,
+                    index: index - self.data.len(),
+                    position: 0,
+                    error: Some(error),
+                };
+                self.source = Some(Arc::new(Mutex::new(Box::pin(injected))));
+            }
+            None => self.data.push_back(Err(error)),
+        }
+        self
+    }
+
+    /// Trailers returned once all frames have been consumed.
+    pub fn with_trailers(mut self, trailers: http::HeaderMap) -> Self {
+        self.trailers = Some(trailers);
+        self
+    }
+
+    /// Run `on_drop` once the body, and every clone of it, has been dropped.
+    pub fn with_drop_callback<F>(mut self, on_drop: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.on_drop = Some(Arc::new(DropGuard(Mutex::new(Some(Box::new(on_drop))))));
+        self
+    }
+
+    /// Number of frames not yet read; polling removes frames from the body.
+    ///
+    /// For a stream-backed body this adds the lower bound of the stream's size hint.
     pub fn len(&self) -> usize {
-        self.data.len()
+        self.data.len() + self.source_size_hint().0
     }
 
+    /// Whether no frames remain. A stream-backed body is only empty once its stream is known to be
+    /// exhausted.
     pub fn is_empty(&self) -> bool {
-        self.data.is_empty()
+        self.data.is_empty() && self.source_size_hint().1 == Some(0)
     }
 
-    // see: https://github.com/hyperium/tonic/blob/1b03ece2a81cb7e8b1922b3c3c1f496bd402d76c/tonic/src/codec/encode.rs#L52
-    fn encode(msg: impl Message) -> Bytes {
-        let mut buf = BytesMut::with_capacity(256);
+    fn source_size_hint(&self) -> (usize, Option<usize>) {
+        match &self.source {
+            Some(source) => lock(source).size_hint(),
+            None => (0, Some(0)),
+        }
+    }
+
+    pub(crate) fn encode(msg: &impl Message) -> Bytes {
+        let mut buf = BytesMut::new();
+        Self::encode_into(&mut buf, msg);
+        buf.freeze()
+    }
+
+    pub(crate) fn encode_into(buf: &mut BytesMut, msg: &impl Message) {
+        buf.reserve(HEADER_SIZE + msg.encoded_len());
+        write_frame(buf, false, |buf| {
+            msg.encode(buf).expect("BytesMut grows on demand")
+        });
+    }
 
-        buf.reserve(5);
-        unsafe {
-            buf.advance_mut(5);
+    #[cfg(feature = "compression")]
+    pub(crate) fn encode_compressed(msg: &impl Message, encoding: CompressionEncoding) -> Bytes {
+        let mut buf = BytesMut::new();
+        Self::encode_compressed_into(&mut buf, msg, encoding);
+        buf.freeze()
+    }
+
+    #[cfg(feature = "compression")]
+    pub(crate) fn encode_compressed_into(
+        buf: &mut BytesMut,
+        msg: &impl Message,
+        encoding: CompressionEncoding,
+    ) {
+        let compressed = match encoding {
+            CompressionEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&msg.encode_to_vec()).unwrap();
+                encoder.finish().unwrap()
+            }
+            _ => panic!(
+                "unsupported compression encoding {:?}, only gzip is supported",
+                encoding
+            ),
+        };
+
+        write_frame(buf, true, |buf| buf.put_slice(&compressed));
+    }
+}
+
+// compression flag (1 byte) + message length (4 bytes)
+pub(crate) const HEADER_SIZE: usize = 5;
+
+// Append one gRPC frame to `buf`, with the payload written by `write_payload`.
+// see: https://github.com/hyperium/tonic/blob/1b03ece2a81cb7e8b1922b3c3c1f496bd402d76c/tonic/src/codec/encode.rs#L52
+pub(crate) fn write_frame(
+    buf: &mut BytesMut,
+    compressed: bool,
+    write_payload: impl FnOnce(&mut BytesMut),
+) {
+    let start = buf.len();
+    buf.put_u8(u8::from(compressed));
+    buf.put_u32(0); // patched once the payload length is known
+    write_payload(buf);
+    let len = (buf.len() - start - HEADER_SIZE) as u32;
+    buf[start + 1..start + HEADER_SIZE].copy_from_slice(&len.to_be_bytes());
+}
+
+fn lock(source: &Mutex<FrameStream>) -> MutexGuard<'_, FrameStream> {
+    source
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Replaces the item at `index` of a shared frame stream with `error`, or appends `error` if the
+// stream ends before reaching it.
+struct ErrorAtFrame {
+    inner: Arc<Mutex<FrameStream>>,
+    index: usize,
+    position: usize,
+    error: Option<Status>,
+}
+
+impl Stream for ErrorAtFrame {
+    type Item = Result<Bytes, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let next = ready!(lock(&self.inner).poll_next_unpin(cx));
+        match next {
+            Some(frame) => {
+                let position = self.position;
+                self.position += 1;
+                match self.error.take() {
+                    Some(error) if position == self.index => Poll::Ready(Some(Err(error))),
+                    error => {
+                        self.error = error;
+                        Poll::Ready(Some(frame))
+                    }
+                }
+            }
+            None => Poll::Ready(self.error.take().map(Err)),
         }
-        msg.encode(&mut buf).unwrap();
-        {
-            let len = buf.len() - 5;
-            let mut buf = &mut buf[..5];
-            buf.put_u8(0); // byte must be 0, reserve doesn't auto-zero
-            buf.put_u32(len as u32);
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = lock(&self.inner).size_hint();
+        let pending = usize::from(self.error.is_some());
+        (lower, upper.map(|upper| upper + pending))
+    }
+}
+
+type DropCallback = Box<dyn FnOnce() + Send>;
+
+struct DropGuard(Mutex<Option<DropCallback>>);
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        let callback = match self.0.get_mut() {
+            Ok(callback) => callback.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        };
+        if let Some(callback) = callback {
+            callback();
         }
-        buf.freeze()
+    }
+}
+
+impl fmt::Debug for MockBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes: usize = self
+            .data
+            .iter()
+            .map(|frame| frame.as_ref().map_or(0, Bytes::len))
+            .sum();
+        let mut debug = f.debug_struct("MockBody");
+        debug
+            .field("frames", &self.data.len())
+            .field("bytes", &bytes);
+        if self.source.is_some() {
+            debug.field("source", &format_args!("Stream"));
+        }
+        debug.finish()
     }
 }
 
@@ -62,21 +416,22 @@ impl Body for MockBody {
 
     fn poll_data(
         mut self: Pin<&mut Self>,
-        _: &mut Context<'_>,
+        cx: &mut Context<'_>,
     ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
-        if !self.is_empty() {
-            let msg = self.data.pop_front().unwrap();
-            Poll::Ready(Some(Ok(msg)))
-        } else {
-            Poll::Ready(None)
+        if let Some(frame) = self.data.pop_front() {
+            return Poll::Ready(Some(frame));
+        }
+        match &self.source {
+            Some(source) => lock(source).poll_next_unpin(cx),
+            None => Poll::Ready(None),
         }
     }
 
     fn poll_trailers(
-        self: Pin<&mut Self>,
+        mut self: Pin<&mut Self>,
         _: &mut Context<'_>,
     ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
-        Poll::Ready(Ok(None))
+        Poll::Ready(Ok(self.trailers.take()))
     }
 }
 /// A [`Decoder`] that knows how to decode `U`.
@@ -0,0 +1,201 @@
+use prost::Message;
+use tonic::{
+    metadata::{BinaryMetadataValue, MetadataKey, MetadataMap, MetadataValue},
+    Extensions, Request, Streaming,
+};
+
+use crate::streaming_request;
+
+/// Fluent builder for non-streaming test requests
+///
+/// Usage:
+/// ```
+/// use tonic_mock::RequestBuilder;
+///
+/// let request = RequestBuilder::new("hello".to_string())
+///     .metadata("x-request-id", "42")
+///     .binary_metadata("trace-bin", b"\x00\x01")
+///     .extension(7u32)
+///     .build();
+///
+/// assert_eq!(request.metadata().get("x-request-id").unwrap(), "42");
+/// assert_eq!(request.metadata().get_bin("trace-bin").unwrap().to_bytes().unwrap().as_ref(), b"\x00\x01");
+/// assert_eq!(request.extensions().get::<u32>(), Some(&7));
+/// assert_eq!(request.get_ref(), "hello");
+/// ```
+pub struct RequestBuilder<T> {
+    message: T,
+    metadata: MetadataMap,
+    extensions: Extensions,
+}
+
+impl<T> RequestBuilder<T> {
+    pub fn new(message: T) -> Self {
+        Self {
+            message,
+            metadata: MetadataMap::new(),
+            extensions: Extensions::default(),
+        }
+    }
+
+    /// Insert an ASCII metadata entry. Panics if the key or value is invalid.
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        insert_ascii(&mut self.metadata, key, value);
+        self
+    }
+
+    /// Insert a binary metadata entry. The key must end with `-bin`.
+    pub fn binary_metadata(mut self, key: &str, value: &[u8]) -> Self {
+        insert_binary(&mut self.metadata, key, value);
+        self
+    }
+
+    pub fn extension<E: Clone + Send + Sync + 'static>(mut self, ext: E) -> Self {
+        self.extensions.insert(ext);
+        self
+    }
+
+    pub fn build(self) -> Request<T> {
+        Request::from_parts(self.metadata, self.extensions, self.message)
+    }
+}
+
+/// Fluent builder for streaming test requests
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic_mock::StreamingRequestBuilder;
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let request = StreamingRequestBuilder::new()
+///     .messages(vec![Event { id: 1 }, Event { id: 2 }])
+///     .metadata("authorization", "Bearer token")
+///     .build();
+/// assert_eq!(request.metadata().get("authorization").unwrap(), "Bearer token");
+///
+/// let mut stream = request.into_inner();
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     assert_eq!(stream.message().await.unwrap(), Some(Event { id: 1 }));
+///     assert_eq!(stream.message().await.unwrap(), Some(Event { id: 2 }));
+///     assert_eq!(stream.message().await.unwrap(), None);
+/// });
+/// ```
+#[derive(Default)]
+pub struct StreamingRequestBuilder<T> {
+    messages: Vec<T>,
+    metadata: MetadataMap,
+    extensions: Extensions,
+}
+
+impl<T> StreamingRequestBuilder<T>
+where
+    T: Message + Default + 'static,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn messages(mut self, msgs: Vec<T>) -> Self {
+        self.messages = msgs;
+        self
+    }
+
+    /// Insert an ASCII metadata entry. Panics if the key or value is invalid.
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        insert_ascii(&mut self.metadata, key, value);
+        self
+    }
+
+    /// Insert a binary metadata entry. The key must end with `-bin`.
+    pub fn binary_metadata(mut self, key: &str, value: &[u8]) -> Self {
+        insert_binary(&mut self.metadata, key, value);
+        self
+    }
+
+    pub fn extension<E: Clone + Send + Sync + 'static>(mut self, ext: E) -> Self {
+        self.extensions.insert(ext);
+        self
+    }
+
+    pub fn build(self) -> Request<Streaming<T>> {
+        let stream = streaming_request(self.messages).into_inner();
+        Request::from_parts(self.metadata, self.extensions, stream)
+    }
+}
+
+pub(crate) fn insert_ascii(metadata: &mut MetadataMap, key: &str, value: &str) {
+    let key: MetadataKey<_> = key.parse().expect("invalid ascii metadata key");
+    let value: MetadataValue<_> = value.parse().expect("invalid ascii metadata value");
+    metadata.insert(key, value);
+}
+
+pub(crate) fn insert_binary(metadata: &mut MetadataMap, key: &str, value: &[u8]) {
+    let key = MetadataKey::from_bytes(key.as_bytes()).expect("invalid binary metadata key");
+    metadata.insert_bin(key, BinaryMetadataValue::from_bytes(value));
+}
+
+/// A closure that mutates a request before it is handed to the service
+pub type RequestInterceptor<T> = Box<dyn FnMut(&mut Request<T>) + Send>;
+
+/// Composes several [`RequestInterceptor`]s, applied in the order they were added
+///
+/// Usage:
+/// ```
+/// use tonic::Request;
+/// use tonic_mock::RequestInterceptorChain;
+///
+/// let mut interceptor = RequestInterceptorChain::new()
+///     .add(Box::new(|req: &mut Request<()>| {
+///         req.metadata_mut().insert("authorization", "Bearer token".parse().unwrap());
+///     }))
+///     .add(Box::new(|req: &mut Request<()>| {
+///         req.metadata_mut().insert("x-trace-id", "abc".parse().unwrap());
+///     }))
+///     .into_interceptor();
+///
+/// let mut request = Request::new(());
+/// interceptor(&mut request);
+/// assert_eq!(request.metadata().get("authorization").unwrap(), "Bearer token");
+/// assert_eq!(request.metadata().get("x-trace-id").unwrap(), "abc");
+/// ```
+pub struct RequestInterceptorChain<T>(Vec<RequestInterceptor<T>>);
+
+impl<T> RequestInterceptorChain<T> {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, interceptor: RequestInterceptor<T>) -> Self {
+        self.0.push(interceptor);
+        self
+    }
+
+    /// Run every interceptor on `request`, in insertion order.
+    pub fn apply(&mut self, request: &mut Request<T>) {
+        for interceptor in self.0.iter_mut() {
+            interceptor(request);
+        }
+    }
+
+    /// Collapse the chain into a single interceptor.
+    pub fn into_interceptor(mut self) -> RequestInterceptor<T>
+    where
+        T: 'static,
+    {
+        Box::new(move |request| self.apply(request))
+    }
+}
+
+impl<T> Default for RequestInterceptorChain<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
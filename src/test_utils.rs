@@ -0,0 +1,733 @@
+//! Helpers for building test fixtures such as canned streaming responses.
+
+use futures::{
+    future::{ready, Ready},
+    stream, Future, Stream, StreamExt,
+};
+use prost::Message;
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use tonic::{
+    metadata::{Ascii, KeyAndValueRef, MetadataKey, MetadataMap},
+    Request, Response, Status, Streaming,
+};
+
+use crate::{
+    request::{insert_ascii, insert_binary},
+    stream_to_vec, streaming_request, MockBody, ProstDecoder, StreamResponse, StreamResponseInner,
+};
+
+#[cfg(feature = "proptest")]
+pub mod proptest;
+
+/// Create a streaming response that cycles through `items` until `total` items were emitted
+///
+/// Useful for services that serve from a ring buffer or sliding window. An empty `items`
+/// produces an empty stream.
+///
+/// Usage:
+/// ```
+/// use tonic_mock::test_utils::create_stream_response_cyclic;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let items = vec![ResponsePush { code: 0 }, ResponsePush { code: 1 }];
+/// let response = create_stream_response_cyclic(items, 5);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+///
+/// let result = rt.block_on(async { tonic_mock::stream_to_vec(response).await });
+/// let codes: Vec<i32> = result.into_iter().map(|v| v.unwrap().code).collect();
+/// assert_eq!(codes, vec![0, 1, 0, 1, 0]);
+/// ```
+pub fn create_stream_response_cyclic<T>(
+    items: Vec<T>,
+    total: usize,
+) -> Response<StreamResponseInner<T>>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    let output = stream::iter(items.into_iter().cycle().take(total).map(Ok));
+    Response::new(Box::pin(output) as StreamResponseInner<T>)
+}
+
+/// Create a streaming response where `delays[i]` is waited before yielding `items[i]`
+///
+/// `None` yields the item immediately. Panics if `items` and `delays` differ in length.
+///
+/// Usage:
+/// ```
+/// use std::time::{Duration, Instant};
+/// use tonic_mock::test_utils::create_slow_intermittent_stream;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let items = vec![ResponsePush { code: 0 }, ResponsePush { code: 1 }];
+/// let delays = vec![None, Some(Duration::from_millis(50))];
+/// let response = create_slow_intermittent_stream(items, delays);
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// let start = Instant::now();
+/// let result = rt.block_on(async { tonic_mock::stream_to_vec(response).await });
+/// assert_eq!(result.len(), 2);
+/// assert!(start.elapsed() >= Duration::from_millis(50));
+/// ```
+pub fn create_slow_intermittent_stream<T>(
+    items: Vec<T>,
+    delays: Vec<Option<Duration>>,
+) -> Response<StreamResponseInner<T>>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    assert_eq!(
+        items.len(),
+        delays.len(),
+        "items and delays must have the same length"
+    );
+    let output = stream::iter(items.into_iter().zip(delays)).then(|(item, delay)| async move {
+        if let Some(delay) = delay {
+            tokio::time::sleep(delay).await;
+        }
+        Ok(item)
+    });
+    Response::new(Box::pin(output) as StreamResponseInner<T>)
+}
+
+/// Create a streaming response yielding `responses`, with the given initial metadata attached
+///
+/// Usage:
+/// ```
+/// use tonic_mock::test_utils::create_stream_response_with_initial_metadata;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let response = create_stream_response_with_initial_metadata(
+///     vec![ResponsePush { code: 0 }],
+///     &[("x-next-page-token", "abc")],
+/// );
+/// assert_eq!(response.metadata().get("x-next-page-token").unwrap(), "abc");
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// let result = rt.block_on(async { tonic_mock::stream_to_vec(response).await });
+/// assert_eq!(result.len(), 1);
+/// ```
+pub fn create_stream_response_with_initial_metadata<T>(
+    responses: Vec<T>,
+    metadata: &[(&str, &str)],
+) -> Response<StreamResponseInner<T>>
+where
+    T: Send + Sync + 'static,
+{
+    let output = stream::iter(responses.into_iter().map(Ok));
+    let mut response = Response::new(Box::pin(output) as StreamResponseInner<T>);
+    for (key, value) in metadata {
+        let key: MetadataKey<Ascii> = key.parse().expect("invalid ascii metadata key");
+        let value = value.parse().expect("invalid ascii metadata value");
+        response.metadata_mut().append(key, value);
+    }
+    response
+}
+
+/// Create a streaming response fed by the returned channel sender
+///
+/// Messages pushed into the sender are yielded by the stream; dropping the sender ends it.
+/// The channel buffers up to 32 items.
+///
+/// Usage:
+/// ```
+/// use tonic::Status;
+/// use tonic_mock::test_utils::create_stream_response_from_channel;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     let (response, tx) = create_stream_response_from_channel::<ResponsePush>();
+///     tx.send(Ok(ResponsePush { code: 0 })).await.unwrap();
+///     tx.send(Err(Status::cancelled("feed closed"))).await.unwrap();
+///     drop(tx);
+///
+///     let result = tonic_mock::stream_to_vec(response).await;
+///     assert_eq!(result.len(), 2);
+///     assert!(result[1].is_err());
+/// });
+/// ```
+pub fn create_stream_response_from_channel<T>() -> (
+    Response<StreamResponseInner<T>>,
+    mpsc::Sender<Result<T, Status>>,
+)
+where
+    T: Clone + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(32);
+    let output = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item, rx))
+    });
+    (
+        Response::new(Box::pin(output) as StreamResponseInner<T>),
+        tx,
+    )
+}
+
+/// Assert that the keys of `responses` are monotonically non-decreasing
+///
+/// Usage:
+/// ```
+/// use tonic_mock::test_utils::assert_responses_ordered;
+///
+/// assert_responses_ordered(&[(1, "a"), (1, "b"), (3, "c")], |r| r.0);
+/// ```
+pub fn assert_responses_ordered<T, K, F>(responses: &[T], key_fn: F)
+where
+    K: Ord,
+    F: Fn(&T) -> K,
+{
+    for (i, pair) in responses.windows(2).enumerate() {
+        assert!(
+            key_fn(&pair[1]) >= key_fn(&pair[0]),
+            "response {} is ordered before response {}",
+            i + 1,
+            i
+        );
+    }
+}
+
+/// Assert that the keys of `responses` are strictly increasing
+///
+/// Usage:
+/// ```should_panic
+/// use tonic_mock::test_utils::assert_responses_strictly_ordered;
+///
+/// // equal keys are rejected
+/// assert_responses_strictly_ordered(&[1, 2, 2], |r| *r);
+/// ```
+pub fn assert_responses_strictly_ordered<T, K, F>(responses: &[T], key_fn: F)
+where
+    K: Ord,
+    F: Fn(&T) -> K,
+{
+    for (i, pair) in responses.windows(2).enumerate() {
+        assert!(
+            key_fn(&pair[1]) > key_fn(&pair[0]),
+            "response {} is not strictly after response {}",
+            i + 1,
+            i
+        );
+    }
+}
+
+/// Build the input of a client-streaming call; same as [`streaming_request`]
+///
+/// Usage:
+/// ```
+/// use tonic_mock::test_utils::create_client_streaming_request;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let mut stream = create_client_streaming_request(vec![Event { id: 1 }]).into_inner();
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     assert_eq!(stream.message().await.unwrap(), Some(Event { id: 1 }));
+/// });
+/// ```
+pub fn create_client_streaming_request<T>(messages: Vec<T>) -> Request<Streaming<T>>
+where
+    T: Message + Default + 'static,
+{
+    streaming_request(messages)
+}
+
+/// Build the input side of a bidirectional-streaming call; same as [`streaming_request`]
+pub fn create_bidirectional_streaming_request<T>(messages: Vec<T>) -> Request<Streaming<T>>
+where
+    T: Message + Default + 'static,
+{
+    streaming_request(messages)
+}
+
+/// Capture the messages of a streaming request and return a replayable copy of it
+///
+/// The incoming stream is drained and its messages re-encoded into a fresh request that keeps
+/// the original metadata and extensions. Capturing stops at the first stream error; the
+/// captured messages exclude it, but the replay yields the same error after them so the service
+/// still observes the failure.
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic_mock::{streaming_request, test_utils::capture_streaming_request};
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let request = streaming_request(vec![Event { id: 1 }, Event { id: 2 }]);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     let (request, captured) = capture_streaming_request(request).await;
+///     assert_eq!(captured, vec![Event { id: 1 }, Event { id: 2 }]);
+///
+///     // the request can still be handed to the service
+///     let mut stream = request.into_inner();
+///     assert_eq!(stream.message().await.unwrap(), Some(Event { id: 1 }));
+/// });
+///
+/// // a stream error is kept in the replay
+/// let request = tonic_mock::streaming_request_with_error_at(
+///     vec![Event { id: 0 }, Event { id: 1 }],
+///     1,
+///     tonic::Status::data_loss("connection reset"),
+/// );
+/// rt.block_on(async {
+///     let (request, captured) = capture_streaming_request(request).await;
+///     assert_eq!(captured, vec![Event { id: 0 }]);
+///
+///     let mut stream = request.into_inner();
+///     assert_eq!(stream.message().await.unwrap(), Some(Event { id: 0 }));
+///     assert_eq!(stream.message().await.unwrap_err().code(), tonic::Code::DataLoss);
+/// });
+/// ```
+pub async fn capture_streaming_request<T>(
+    request: Request<Streaming<T>>,
+) -> (Request<Streaming<T>>, Vec<T>)
+where
+    T: Message + Default + Clone + Send + 'static,
+{
+    let (request, messages, _) = capture_streaming_request_with_metadata(request).await;
+    (request, messages)
+}
+
+/// Like [`capture_streaming_request`], but also returns a copy of the request metadata
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic_mock::{test_utils::capture_streaming_request_with_metadata, StreamingRequestBuilder};
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let request = StreamingRequestBuilder::new()
+///     .messages(vec![Event { id: 1 }])
+///     .metadata("x-user", "alice")
+///     .build();
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     let (request, captured, metadata) = capture_streaming_request_with_metadata(request).await;
+///     assert_eq!(captured.len(), 1);
+///     assert_eq!(metadata.get("x-user").unwrap(), "alice");
+///     assert_eq!(request.metadata().get("x-user").unwrap(), "alice");
+/// });
+/// ```
+pub async fn capture_streaming_request_with_metadata<T>(
+    request: Request<Streaming<T>>,
+) -> (Request<Streaming<T>>, Vec<T>, MetadataMap)
+where
+    T: Message + Default + Clone + Send + 'static,
+{
+    let (metadata, extensions, mut stream) = request.into_parts();
+    let mut messages = Vec::new();
+    let mut error = None;
+    loop {
+        match stream.message().await {
+            Ok(Some(msg)) => messages.push(msg),
+            Ok(None) => break,
+            Err(status) => {
+                error = Some(status);
+                break;
+            }
+        }
+    }
+
+    let body = match error {
+        Some(status) => MockBody::with_error_at(messages.clone(), messages.len(), status),
+        None => MockBody::new(messages.clone()),
+    };
+    let replay = Streaming::new_request(ProstDecoder::<T>::new(), body, None, None);
+    let captured_metadata = metadata.clone();
+    (
+        Request::from_parts(metadata, extensions, replay),
+        messages,
+        captured_metadata,
+    )
+}
+
+/// Fluent builder for a [`MetadataMap`]
+///
+/// Usage:
+/// ```
+/// use tonic::Request;
+/// use tonic_mock::test_utils::TestMetadata;
+///
+/// let metadata = TestMetadata::new()
+///     .ascii("authorization", "Bearer token")
+///     .binary("trace-bin", b"\x00\x01");
+///
+/// let mut request = Request::new(());
+/// request.metadata_mut().insert("x-request-id", "42".parse().unwrap());
+/// metadata.attach_to(&mut request);
+/// assert_eq!(request.metadata().get("authorization").unwrap(), "Bearer token");
+/// assert_eq!(request.metadata().get("x-request-id").unwrap(), "42");
+///
+/// let map = metadata.into_inner();
+/// assert_eq!(map.len(), 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TestMetadata(MetadataMap);
+
+impl TestMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert an ASCII metadata entry. Panics if the key or value is invalid.
+    pub fn ascii(mut self, key: &str, value: &str) -> Self {
+        insert_ascii(&mut self.0, key, value);
+        self
+    }
+
+    /// Insert a binary metadata entry. The key must end with `-bin`.
+    pub fn binary(mut self, key: &str, value: &[u8]) -> Self {
+        insert_binary(&mut self.0, key, value);
+        self
+    }
+
+    /// Copy the entries into `request`, replacing any existing values for the same keys.
+    pub fn attach_to<T>(&self, request: &mut Request<T>) -> &Self {
+        let mut headers = std::mem::take(request.metadata_mut()).into_headers();
+        headers.extend(self.0.clone().into_headers());
+        *request.metadata_mut() = MetadataMap::from_headers(headers);
+        self
+    }
+
+    pub fn into_inner(self) -> MetadataMap {
+        self.0
+    }
+}
+
+/// Assert that `response` carries the ASCII metadata entry `key: value`
+///
+/// On failure the panic message lists all metadata present on the response.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+/// use tonic_mock::test_utils::assert_metadata_contains;
+///
+/// let mut response = Response::new(());
+/// response.metadata_mut().insert("x-request-id", "42".parse().unwrap());
+/// assert_metadata_contains(&response, "x-request-id", "42");
+/// ```
+pub fn assert_metadata_contains<T>(response: &Response<T>, key: &str, value: &str) {
+    let metadata = response.metadata();
+    let actual = metadata.get(key).map(|v| v.to_str());
+    assert!(
+        matches!(actual, Some(Ok(v)) if v == value),
+        "expected metadata {:?} = {:?}, present metadata: {:?}",
+        key,
+        value,
+        metadata_entries(metadata)
+    );
+}
+
+/// Assert that the metadata of `response` is exactly the given set of ASCII entries
+///
+/// Order does not matter. Binary entries always count as unexpected.
+///
+/// Usage:
+/// ```
+/// use tonic::Response;
+/// use tonic_mock::test_utils::assert_metadata_eq;
+///
+/// let mut response = Response::new(());
+/// response.metadata_mut().insert("x-a", "1".parse().unwrap());
+/// response.metadata_mut().insert("x-b", "2".parse().unwrap());
+/// assert_metadata_eq(&response, &[("x-b", "2"), ("x-a", "1")]);
+/// ```
+pub fn assert_metadata_eq<T>(response: &Response<T>, expected: &[(&str, &str)]) {
+    let mut actual = metadata_entries(response.metadata());
+    actual.sort();
+    let mut expected: Vec<(String, String)> = expected
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    expected.sort();
+    assert_eq!(actual, expected, "response metadata mismatch");
+}
+
+fn metadata_entries(metadata: &MetadataMap) -> Vec<(String, String)> {
+    metadata
+        .iter()
+        .map(|entry| match entry {
+            KeyAndValueRef::Ascii(k, v) => (
+                k.to_string(),
+                v.to_str().unwrap_or("<non-ascii>").to_string(),
+            ),
+            KeyAndValueRef::Binary(k, _) => (k.to_string(), "<binary>".to_string()),
+        })
+        .collect()
+}
+
+/// Wrap a service handler so every call increments the returned counter before delegating
+///
+/// Usage:
+/// ```
+/// use std::sync::atomic::Ordering;
+/// use tonic::{Request, Response, Status};
+/// use tonic_mock::test_utils::service_call_counter;
+///
+/// let (service, calls) = service_call_counter(|req: Request<u32>| async move {
+///     Ok::<_, Status>(Response::new(req.into_inner() * 2))
+/// });
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     assert_eq!(service(Request::new(1)).await.unwrap().into_inner(), 2);
+///     assert_eq!(service(Request::new(2)).await.unwrap().into_inner(), 4);
+/// });
+/// assert_eq!(calls.load(Ordering::SeqCst), 2);
+/// ```
+pub fn service_call_counter<Req, Resp, F, Fut>(
+    handler: F,
+) -> (impl Fn(Request<Req>) -> Fut, Arc<AtomicUsize>)
+where
+    F: Fn(Request<Req>) -> Fut,
+    Fut: Future<Output = Result<Response<Resp>, Status>>,
+{
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let service = move |request| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        handler(request)
+    };
+    (service, calls)
+}
+
+/// Create a bidirectional streaming handler that answers each request message with `mapper(msg, index)`
+///
+/// Request stream errors are passed through to the response stream.
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic_mock::{streaming_request, stream_to_vec, test_utils::echo_streaming_service};
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let service = echo_streaming_service(|msg: Event, i| Event { id: msg.id * 10 + i as i32 });
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     let response = service(streaming_request(vec![Event { id: 1 }, Event { id: 2 }]))
+///         .await
+///         .unwrap();
+///     let ids: Vec<i32> = stream_to_vec(response).await.into_iter().map(|v| v.unwrap().id).collect();
+///     assert_eq!(ids, vec![10, 21]);
+/// });
+/// ```
+pub fn echo_streaming_service<T, R, F>(
+    mapper: F,
+) -> impl Fn(Request<Streaming<T>>) -> Ready<Result<StreamResponse<R>, Status>>
+where
+    T: Message + Default + 'static,
+    R: Send + 'static,
+    F: Fn(T, usize) -> R + Send + Sync + 'static,
+{
+    let mapper = Arc::new(mapper);
+    move |request| {
+        let mapper = mapper.clone();
+        let output = request
+            .into_inner()
+            .enumerate()
+            .map(move |(i, msg)| msg.map(|msg| mapper(msg, i)));
+        let output = SyncStream(Mutex::new(output));
+        ready(Ok(
+            Response::new(Box::pin(output) as StreamResponseInner<R>),
+        ))
+    }
+}
+
+/// Create a bidirectional streaming handler that echoes every request message unchanged
+///
+/// Usage:
+/// ```
+/// use prost::Message;
+/// use tonic_mock::{streaming_request, stream_to_vec, test_utils::identity_streaming_service};
+///
+/// #[derive(Clone, PartialEq, Message)]
+/// pub struct Event {
+///     #[prost(int32, tag = "1")]
+///     pub id: i32,
+/// }
+///
+/// let service = identity_streaming_service::<Event>();
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     let response = service(streaming_request(vec![Event { id: 1 }])).await.unwrap();
+///     assert_eq!(stream_to_vec(response).await[0].as_ref().unwrap(), &Event { id: 1 });
+/// });
+/// ```
+pub fn identity_streaming_service<T>(
+) -> impl Fn(Request<Streaming<T>>) -> Ready<Result<StreamResponse<T>, Status>>
+where
+    T: Message + Default + 'static,
+{
+    echo_streaming_service(|msg, _| msg)
+}
+
+// `Streaming` is not `Sync`, which `StreamResponseInner` requires. The mutex is never locked:
+// polling goes through `get_mut`, so it only serves to make the wrapper `Sync`.
+struct SyncStream<S>(Mutex<S>);
+
+impl<S: Stream + Unpin> Stream for SyncStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut().0.get_mut() {
+            Ok(stream) => stream.poll_next_unpin(cx),
+            Err(poisoned) => poisoned.into_inner().poll_next_unpin(cx),
+        }
+    }
+}
+
+/// Call a server-streaming service and collect its responses
+///
+/// An error returned by the call itself is turned into a single-element result, so callers can
+/// assert on one `Vec` whichever way the service fails.
+///
+/// Usage:
+/// ```
+/// use tonic::{Request, Response, Status};
+/// use tonic_mock::{test_utils::run_streaming_test, StreamResponse, StreamResponseInner};
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// async fn count_to(req: Request<i32>) -> Result<StreamResponse<ResponsePush>, Status> {
+///     let n = req.into_inner();
+///     if n < 0 {
+///         return Err(Status::invalid_argument("negative"));
+///     }
+///     let output = futures::stream::iter((0..n).map(|code| Ok(ResponsePush { code })));
+///     Ok(Response::new(Box::pin(output) as StreamResponseInner<ResponsePush>))
+/// }
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(async {
+///     assert_eq!(run_streaming_test(count_to, Request::new(3)).await.len(), 3);
+///     let failed = run_streaming_test(count_to, Request::new(-1)).await;
+///     assert_eq!(failed[0].as_ref().unwrap_err().message(), "negative");
+/// });
+/// ```
+pub async fn run_streaming_test<Req, Resp, F, Fut>(
+    service_fn: F,
+    request: Request<Req>,
+) -> Vec<Result<Resp, Status>>
+where
+    Resp: Message + Default + 'static,
+    F: Fn(Request<Req>) -> Fut,
+    Fut: Future<Output = Result<StreamResponse<Resp>, Status>>,
+{
+    match service_fn(request).await {
+        Ok(response) => stream_to_vec(response).await,
+        Err(status) => vec![Err(status)],
+    }
+}
+
+/// Assert that a streaming response yields no items
+///
+/// Usage:
+/// ```
+/// use tonic_mock::test_utils::{assert_stream_empty, create_stream_response_cyclic};
+///
+/// let response = create_stream_response_cyclic(Vec::<()>::new(), 10);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(assert_stream_empty(response));
+/// ```
+pub async fn assert_stream_empty<T>(response: StreamResponse<T>)
+where
+    T: Message + Default + 'static,
+{
+    if let Some(item) = response.into_inner().next().await {
+        panic!("expected an empty stream, first item: {:?}", item);
+    }
+}
+
+/// Assert that the first item of a streaming response is an error matching `expected`
+///
+/// The code and message of the error are compared.
+///
+/// Usage:
+/// ```
+/// use tonic::{Response, Status};
+/// use tonic_mock::{test_utils::assert_stream_starts_with_error, StreamResponseInner};
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct ResponsePush {
+///     #[prost(int32, tag = "1")]
+///     pub code: i32,
+/// }
+///
+/// let output = futures::stream::iter(vec![Err::<ResponsePush, _>(Status::aborted("conflict"))]);
+/// let response = Response::new(Box::pin(output) as StreamResponseInner<ResponsePush>);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(assert_stream_starts_with_error(response, Status::aborted("conflict")));
+/// ```
+pub async fn assert_stream_starts_with_error<T>(response: StreamResponse<T>, expected: Status)
+where
+    T: Message + Default + 'static,
+{
+    match response.into_inner().next().await {
+        Some(Err(status)) => assert!(
+            status.code() == expected.code() && status.message() == expected.message(),
+            "expected the stream to start with {:?}: {:?}, got {:?}: {:?}",
+            expected.code(),
+            expected.message(),
+            status.code(),
+            status.message()
+        ),
+        Some(Ok(item)) => panic!(
+            "expected the stream to start with an error, got item: {:?}",
+            item
+        ),
+        None => panic!("expected the stream to start with an error, got an empty stream"),
+    }
+}
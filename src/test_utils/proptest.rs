@@ -0,0 +1,43 @@
+//! Property-based testing support, enabled with the `proptest` feature.
+
+pub use crate::grpc_mock::grpc_roundtrip as roundtrip;
+
+// lets `prop_assert_roundtrip!` expand in crates that don't depend on proptest under that name
+#[doc(hidden)]
+pub use ::proptest as __proptest;
+
+/// Assert inside a proptest body that a message survives a gRPC encode/decode round-trip
+///
+/// Usage:
+/// ```
+/// use proptest::prelude::*;
+/// use tonic_mock::prop_assert_roundtrip;
+///
+/// #[derive(Clone, PartialEq, ::prost::Message)]
+/// pub struct Event {
+///     #[prost(string, tag = "1")]
+///     pub data: String,
+///     #[prost(int64, tag = "2")]
+///     pub seq: i64,
+/// }
+///
+/// proptest!(|(data in ".*", seq in any::<i64>())| {
+///     prop_assert_roundtrip!(Event { data, seq });
+/// });
+/// ```
+#[macro_export]
+macro_rules! prop_assert_roundtrip {
+    ($message:expr) => {{
+        let message = $message;
+        match $crate::test_utils::proptest::roundtrip(&message) {
+            Ok(decoded) => {
+                $crate::test_utils::proptest::__proptest::prop_assert_eq!(decoded, message)
+            }
+            Err(status) => $crate::test_utils::proptest::__proptest::prop_assert!(
+                false,
+                "round-trip failed: {}",
+                status
+            ),
+        }
+    }};
+}